@@ -0,0 +1,270 @@
+use std::{fmt::Display, path::PathBuf};
+
+use crate::shop;
+
+pub const DEFAULT_LAYOUT: [usize; 3] = [2, 3, 5];
+pub const USAGE: &str = r"Usage: sdii_cw_class_rs [OPTIONS] [run <SCRIPT>]
+
+Commands:
+    run <SCRIPT>             Run the commands in a script file, or standard input when given -,
+                             printing one line of JSON per command
+
+Options:
+    --layout <CAPACITIES>    Comma separated queue capacities, e.g. 2,3,5
+    --load <FILE>            Load program data from a save file on startup, cannot be combined
+                             with --layout or --initial-stock
+    --initial-stock <N>      Stock to open the shop with
+    --operator <NAME>        Name recorded with stock changes, defaults to $USER
+    --gui                    Start in the GUI instead of the text interface
+    --help                   Display this help";
+
+#[derive(Debug)]
+pub struct Options {
+    pub layout: Vec<usize>,
+    pub load_path: Option<PathBuf>,
+    pub initial_stock: Option<usize>,
+    pub operator: Option<String>,
+    pub script_path: Option<PathBuf>,
+    pub gui: bool,
+    pub help: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            layout: DEFAULT_LAYOUT.to_vec(),
+            load_path: None,
+            initial_stock: None,
+            operator: None,
+            script_path: None,
+            gui: false,
+            help: false,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ArgError {
+    UnknownArgument(String),
+    MissingValue(&'static str),
+    EmptyLayout,
+    InvalidCapacity(String),
+    InvalidStock(String),
+    ConflictingOptions(&'static str, &'static str),
+}
+
+impl Display for ArgError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ArgError::UnknownArgument(argument) => {
+                f.write_fmt(format_args!("Unknown argument \"{argument}\"!"))
+            }
+            ArgError::MissingValue(option) => {
+                f.write_fmt(format_args!("{option} requires a value!"))
+            }
+            ArgError::EmptyLayout => f.write_str("The layout needs at least one queue!"),
+            ArgError::InvalidCapacity(capacity) => f.write_fmt(format_args!(
+                "Queue capacity must be a number above 0, found \"{capacity}\"!"
+            )),
+            ArgError::InvalidStock(stock) => f.write_fmt(format_args!(
+                "Initial stock must be a number from 0 to {}, found \"{stock}\"!",
+                shop::STOCK_MAX_THRESHOLD
+            )),
+            ArgError::ConflictingOptions(option1, option2) => {
+                f.write_fmt(format_args!("{option1} cannot be used with {option2}!"))
+            }
+        }
+    }
+}
+
+/// Parses the program arguments, not including the program name.
+pub fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Options, ArgError> {
+    let mut options = Options::default();
+    let mut args = args.into_iter();
+    let mut layout_given = false;
+
+    while let Some(arg) = args.next() {
+        // Both `--option value` and `--option=value` are accepted
+        let (name, inline_value) = match arg.split_once('=') {
+            Some((name, value)) => (name.to_string(), Some(value.to_string())),
+            None => (arg.clone(), None),
+        };
+
+        match name.as_str() {
+            "--layout" => {
+                let value = inline_value
+                    .or_else(|| args.next())
+                    .ok_or(ArgError::MissingValue("--layout"))?;
+                options.layout = parse_layout(&value)?;
+                layout_given = true;
+            }
+            "--load" => {
+                let value = inline_value
+                    .or_else(|| args.next())
+                    .ok_or(ArgError::MissingValue("--load"))?;
+                options.load_path = Some(PathBuf::from(value));
+            }
+            "--initial-stock" => {
+                let value = inline_value
+                    .or_else(|| args.next())
+                    .ok_or(ArgError::MissingValue("--initial-stock"))?;
+                options.initial_stock = Some(parse_stock(&value)?);
+            }
+            "--operator" => {
+                let value = inline_value
+                    .or_else(|| args.next())
+                    .ok_or(ArgError::MissingValue("--operator"))?;
+                options.operator = Some(value);
+            }
+            "run" if inline_value.is_none() && options.script_path.is_none() => {
+                let value = args.next().ok_or(ArgError::MissingValue("run"))?;
+                options.script_path = Some(PathBuf::from(value));
+            }
+            "--gui" if inline_value.is_none() => options.gui = true,
+            "--help" | "-h" if inline_value.is_none() => options.help = true,
+            _ => return Err(ArgError::UnknownArgument(arg)),
+        }
+    }
+
+    // The save file has its own layout and stock, which would silently win over these
+    if options.load_path.is_some() {
+        if layout_given {
+            return Err(ArgError::ConflictingOptions("--load", "--layout"));
+        }
+
+        if options.initial_stock.is_some() {
+            return Err(ArgError::ConflictingOptions("--load", "--initial-stock"));
+        }
+    }
+
+    Ok(options)
+}
+
+pub fn parse_layout(value: &str) -> Result<Vec<usize>, ArgError> {
+    if value.trim().is_empty() {
+        return Err(ArgError::EmptyLayout);
+    }
+
+    value
+        .split(',')
+        .map(|capacity| match capacity.trim().parse::<usize>() {
+            Ok(capacity) if capacity > 0 => Ok(capacity),
+            _ => Err(ArgError::InvalidCapacity(capacity.trim().to_string())),
+        })
+        .collect()
+}
+
+fn parse_stock(value: &str) -> Result<usize, ArgError> {
+    match value.trim().parse::<usize>() {
+        Ok(stock) if stock <= shop::STOCK_MAX_THRESHOLD => Ok(stock),
+        _ => Err(ArgError::InvalidStock(value.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Options, ArgError> {
+        parse_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn defaults_without_arguments() {
+        let options = parse(&[]).unwrap();
+
+        assert_eq!(options.layout, DEFAULT_LAYOUT);
+        assert!(options.load_path.is_none());
+        assert!(options.initial_stock.is_none());
+        assert!(options.script_path.is_none());
+        assert!(!options.gui);
+        assert!(!options.help);
+    }
+
+    #[test]
+    fn takes_values_inline_or_separately() {
+        let options = parse(&[
+            "--layout=1,4",
+            "--initial-stock",
+            "20",
+            "--operator=Sam",
+            "--gui",
+        ])
+        .unwrap();
+
+        assert_eq!(options.layout, [1, 4]);
+        assert_eq!(options.initial_stock, Some(20));
+        assert_eq!(options.operator.as_deref(), Some("Sam"));
+        assert!(options.gui);
+    }
+
+    #[test]
+    fn reads_the_script_to_run() {
+        let options = parse(&["--load", "save.json", "run", "-"]).unwrap();
+
+        assert_eq!(options.load_path, Some(PathBuf::from("save.json")));
+        assert_eq!(options.script_path, Some(PathBuf::from("-")));
+        assert!(matches!(
+            parse(&["run", "a.txt", "run", "b.txt"]),
+            Err(ArgError::UnknownArgument(arg)) if arg == "run"
+        ));
+    }
+
+    #[test]
+    fn rejects_bad_arguments() {
+        assert!(matches!(
+            parse(&["--verbose"]),
+            Err(ArgError::UnknownArgument(arg)) if arg == "--verbose"
+        ));
+        assert!(matches!(
+            parse(&["--gui=yes"]),
+            Err(ArgError::UnknownArgument(_))
+        ));
+        assert!(matches!(
+            parse(&["--layout"]),
+            Err(ArgError::MissingValue("--layout"))
+        ));
+        assert!(matches!(
+            parse(&["--initial-stock", "-1"]),
+            Err(ArgError::InvalidStock(_))
+        ));
+        assert!(matches!(
+            parse(&[
+                "--initial-stock",
+                &(shop::STOCK_MAX_THRESHOLD + 1).to_string()
+            ]),
+            Err(ArgError::InvalidStock(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_a_layout_or_stock_along_with_a_save_file() {
+        assert!(matches!(
+            parse(&["--layout", "2", "--load", "save.json"]),
+            Err(ArgError::ConflictingOptions("--load", "--layout"))
+        ));
+        assert!(matches!(
+            parse(&["--load", "save.json", "--initial-stock", "5"]),
+            Err(ArgError::ConflictingOptions("--load", "--initial-stock"))
+        ));
+    }
+
+    #[test]
+    fn parses_queue_capacities() {
+        assert_eq!(parse_layout("2,3,5").unwrap(), [2, 3, 5]);
+        assert_eq!(parse_layout(" 1 , 2 ").unwrap(), [1, 2]);
+        assert!(matches!(parse_layout(" "), Err(ArgError::EmptyLayout)));
+        assert!(matches!(
+            parse_layout("2,0"),
+            Err(ArgError::InvalidCapacity(capacity)) if capacity == "0"
+        ));
+        assert!(matches!(
+            parse_layout("2,,3"),
+            Err(ArgError::InvalidCapacity(capacity)) if capacity.is_empty()
+        ));
+        assert!(matches!(
+            parse_layout("two"),
+            Err(ArgError::InvalidCapacity(_))
+        ));
+    }
+}
@@ -1,19 +1,170 @@
-use std::fmt::Display;
+use std::{
+    cmp::Ordering,
+    fmt::Display,
+    hash::{Hash, Hasher},
+    sync::atomic::{self, AtomicU64},
+};
 
-#[derive(Default, Debug, Clone, Eq, PartialOrd, Ord)]
+use serde::{Deserialize, Serialize};
+
+use crate::inventory::DEFAULT_PRODUCT;
+
+/// A quantity of one product in a customer's order.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct LineItem {
+    pub product: String,
+    pub quantity: usize,
+}
+
+impl Display for LineItem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("{}:{}", self.product, self.quantity))
+    }
+}
+
+/// How far ahead of others a customer is placed when joining a queue, lowest first.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PriorityLevel {
+    #[default]
+    Regular,
+    Priority,
+    Staff,
+}
+
+impl PriorityLevel {
+    pub const ALL: [PriorityLevel; 3] = [
+        PriorityLevel::Regular,
+        PriorityLevel::Priority,
+        PriorityLevel::Staff,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PriorityLevel::Regular => "regular",
+            PriorityLevel::Priority => "priority",
+            PriorityLevel::Staff => "staff",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|priority| priority.name().eq_ignore_ascii_case(name.trim()))
+    }
+
+    /// A single letter marking the priority in compact views.
+    pub fn initial(&self) -> &'static str {
+        match self {
+            PriorityLevel::Regular => "R",
+            PriorityLevel::Priority => "P",
+            PriorityLevel::Staff => "S",
+        }
+    }
+}
+
+impl Display for PriorityLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The next ID handed to a new customer, kept above every ID loaded so far.
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Identifies a customer for as long as they exist, including across saves. Copies of a customer
+/// share their ID, so a customer is still the same one after their order changes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct CustomerId(pub u64);
+
+impl CustomerId {
+    fn next() -> Self {
+        Self(NEXT_ID.fetch_add(1, atomic::Ordering::Relaxed))
+    }
+
+    /// Makes sure no new customer is given this ID. The largest ID cannot be reserved since
+    /// there would be no ID left to hand out after it.
+    fn reserve(self) -> Option<()> {
+        let next_id = self.0.checked_add(1)?;
+        NEXT_ID.fetch_max(next_id, atomic::Ordering::Relaxed);
+
+        Some(())
+    }
+}
+
+impl Display for CustomerId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "SavedCustomer")]
 pub struct Customer {
+    id: CustomerId,
+    first_name: String,
+    last_name: String,
+    order: Vec<LineItem>,
+    priority: PriorityLevel,
+    /// How many times someone with a higher priority was placed ahead of this customer in their
+    /// queue.
+    overtaken: usize,
+}
+
+/// Loaded customers go through `Customer::with_id`, which turns down the largest ID.
+#[derive(Deserialize)]
+struct SavedCustomer {
+    id: CustomerId,
     first_name: String,
     last_name: String,
-    no_items: usize,
+    order: Vec<LineItem>,
+    priority: PriorityLevel,
+    overtaken: usize,
+}
+
+impl TryFrom<SavedCustomer> for Customer {
+    type Error = String;
+
+    fn try_from(saved: SavedCustomer) -> Result<Self, Self::Error> {
+        let id = saved.id;
+        let mut customer = Customer::with_order(saved.first_name, saved.last_name, saved.order)
+            .with_id(id)
+            .ok_or_else(|| format!("customer id {id} is too large"))?;
+
+        customer.set_overtaken(saved.overtaken);
+        Ok(customer.with_priority(saved.priority))
+    }
 }
 
 impl PartialEq for Customer {
     fn eq(&self, other: &Self) -> bool {
-        self.full_name() == other.full_name()
+        self.id == other.id
     }
+}
+
+impl Eq for Customer {}
+
+impl Hash for Customer {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
+}
 
-    fn ne(&self, other: &Self) -> bool {
-        self.full_name() != other.full_name()
+/// Sorts by name, falling back to the ID so only the same customer compares equal.
+impl Ord for Customer {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.first_name(), self.last_name(), self.id).cmp(&(
+            other.first_name(),
+            other.last_name(),
+            other.id,
+        ))
+    }
+}
+
+impl PartialOrd for Customer {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
     }
 }
 
@@ -23,20 +174,53 @@ impl Display for Customer {
             "{}\n{}\n{}",
             self.first_name(),
             self.last_name(),
-            self.no_items()
+            format_order(self.order())
         ))
     }
 }
 
 impl Customer {
+    /// Creates a customer ordering `no_items` of the default product.
     pub fn new(first_name: String, last_name: String, no_items: usize) -> Self {
+        Self::with_order(
+            first_name,
+            last_name,
+            vec![LineItem {
+                product: DEFAULT_PRODUCT.to_string(),
+                quantity: no_items,
+            }],
+        )
+    }
+
+    pub fn with_order(first_name: String, last_name: String, order: Vec<LineItem>) -> Self {
         Self {
+            id: CustomerId::next(),
             first_name,
             last_name,
-            no_items,
+            order,
+            priority: PriorityLevel::default(),
+            overtaken: 0,
         }
     }
 
+    /// Gives the customer an ID loaded from a save file in place of their new one.
+    /// Gives the customer an ID they were saved with. Returns `None` for the largest ID, which
+    /// is never handed out.
+    pub fn with_id(mut self, id: CustomerId) -> Option<Self> {
+        id.reserve()?;
+        self.id = id;
+        Some(self)
+    }
+
+    pub fn with_priority(mut self, priority: PriorityLevel) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    pub fn id(&self) -> CustomerId {
+        self.id
+    }
+
     pub fn first_name(&self) -> &str {
         self.first_name.as_str()
     }
@@ -49,7 +233,86 @@ impl Customer {
         format!("{} {}", self.first_name(), self.last_name())
     }
 
+    pub fn order(&self) -> &[LineItem] {
+        self.order.as_slice()
+    }
+
+    /// The total quantity across every line item.
     pub fn no_items(&self) -> usize {
-        self.no_items
+        self.order.iter().map(|line_item| line_item.quantity).sum()
+    }
+
+    pub fn set_order(&mut self, order: Vec<LineItem>) {
+        self.order = order;
+    }
+
+    pub fn priority(&self) -> PriorityLevel {
+        self.priority
+    }
+
+    pub fn set_priority(&mut self, priority: PriorityLevel) {
+        self.priority = priority;
+    }
+
+    pub fn overtaken(&self) -> usize {
+        self.overtaken
+    }
+
+    pub fn set_overtaken(&mut self, overtaken: usize) {
+        self.overtaken = overtaken;
+    }
+
+    /// A readable order like `2 Item, 1 Drink`.
+    pub fn order_summary(&self) -> String {
+        summarize_order(self.order())
     }
 }
+
+pub fn summarize_order(order: &[LineItem]) -> String {
+    order
+        .iter()
+        .map(|line_item| format!("{} {}", line_item.quantity, line_item.product))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Writes an order as `product:quantity` pairs separated by commas. An order of only the default
+/// product is written as a plain number so older save files stay readable both ways.
+pub fn format_order(order: &[LineItem]) -> String {
+    match order {
+        [line_item] if line_item.product == DEFAULT_PRODUCT => line_item.quantity.to_string(),
+        order => order
+            .iter()
+            .map(|line_item| line_item.to_string())
+            .collect::<Vec<_>>()
+            .join(","),
+    }
+}
+
+/// Reads an order written by `format_order`. Every quantity must be above 0.
+pub fn parse_order(value: &str) -> Option<Vec<LineItem>> {
+    let value = value.trim();
+
+    if let Ok(quantity) = value.parse::<usize>() {
+        return (quantity > 0).then(|| {
+            vec![LineItem {
+                product: DEFAULT_PRODUCT.to_string(),
+                quantity,
+            }]
+        });
+    }
+
+    value
+        .split(',')
+        .map(|line_item| {
+            let (product, quantity) = line_item.rsplit_once(':')?;
+            let product = product.trim();
+            let quantity = quantity.trim().parse::<usize>().ok()?;
+
+            (!product.is_empty() && quantity > 0).then(|| LineItem {
+                product: product.to_string(),
+                quantity,
+            })
+        })
+        .collect()
+}
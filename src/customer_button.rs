@@ -1,10 +1,11 @@
+use adw::prelude::*;
+use gtk::gdk;
 use gtk::glib;
-use gtk::prelude::*;
 use gtk::subclass::prelude::*;
 
 use std::cell::RefCell;
 
-use crate::customer::Customer;
+use crate::customer::{Customer, CustomerId, PriorityLevel};
 
 glib::wrapper! {
     pub struct CustomerButton(ObjectSubclass<imp::CustomerButton>)
@@ -17,22 +18,56 @@ impl CustomerButton {
         let button: CustomerButton = glib::Object::builder().build();
         let imp = button.imp();
 
-        button.set_label(if customer.is_some() { "O" } else { "X" });
-        button.set_css_classes(if customer.is_some() {
-            &["suggested-action"]
-        } else {
-            &["destructive-action"]
-        });
+        // Customers ahead of others show the initial of their priority and a rounder button
+        match customer.as_ref().map(Customer::priority) {
+            Some(PriorityLevel::Regular) => {
+                button.set_label("O");
+                button.set_css_classes(&["suggested-action"]);
+            }
+            Some(priority) => {
+                button.set_label(priority.initial());
+                button.set_css_classes(&["suggested-action", "pill"]);
+                button.set_tooltip_text(Some(&format!("Priority: {priority}")));
+            }
+            None => {
+                button.set_label("X");
+                button.set_css_classes(&["destructive-action"]);
+            }
+        }
         button.set_sensitive(customer.is_some());
         button.set_margin_start(6);
         button.set_margin_end(6);
 
         if let Some(customer) = customer {
+            let drag_source = gtk::DragSource::new();
+            drag_source.set_actions(gdk::DragAction::MOVE);
+            drag_source.set_content(Some(&gdk::ContentProvider::for_value(
+                &customer.id().to_string().to_value(),
+            )));
+
             imp.customer.replace(Some(customer));
+            button.add_controller(drag_source);
         }
 
         button
     }
+
+    /// Highlights the button when it matches a search and dims it when it does not. Passing `None`
+    /// clears the search styling.
+    pub fn set_search_match(&self, matched: Option<bool>) {
+        self.set_opacity(if matched == Some(false) { 0.3 } else { 1.0 });
+
+        if matched == Some(true) {
+            self.add_css_class("circular");
+        } else {
+            self.remove_css_class("circular");
+        }
+    }
+
+    /// Reads back the ID of the customer on a dragged button.
+    pub fn parse_drag_source(source: &str) -> Option<CustomerId> {
+        source.parse().ok().map(CustomerId)
+    }
 }
 
 mod imp {
@@ -59,29 +94,40 @@ mod imp {
             let customer = self.customer.borrow();
 
             if let Some(customer) = customer.as_ref() {
-                let close_button = gtk::Button::builder()
-                    .label("Close")
-                    .css_classes(["destructive-action"])
-                    .build();
+                let window = self
+                    .obj()
+                    .root()
+                    .unwrap()
+                    .downcast::<gtk::ApplicationWindow>()
+                    .unwrap();
 
                 let dialog = adw::MessageDialog::builder()
                     .heading("Customer")
                     .body(format!(
-                        "Name: {}\nItems: {}",
+                        "ID: {}\nName: {}\nOrder: {}\nPriority: {}",
+                        customer.id(),
                         customer.full_name(),
-                        customer.no_items()
+                        customer.order_summary(),
+                        customer.priority()
                     ))
-                    .extra_child(&close_button)
-                    .transient_for(
-                        self.obj()
-                            .root()
-                            .unwrap()
-                            .downcast_ref::<gtk::ApplicationWindow>()
-                            .unwrap(),
-                    )
+                    .transient_for(&window)
                     .build();
 
-                close_button.connect_clicked(glib::clone!(@weak dialog => move |_| dialog.close()));
+                dialog.add_responses(&[("close", "Close"), ("remove", "Remove")]);
+                dialog.set_response_appearance("remove", adw::ResponseAppearance::Destructive);
+                dialog.set_close_response("close");
+
+                let parameter = customer.id().0;
+                dialog.connect_response(
+                    Some("remove"),
+                    glib::clone!(@weak window => move |_, _| {
+                        ActionGroupExt::activate_action(
+                            &window,
+                            "remove-customer",
+                            Some(&parameter.to_variant()),
+                        );
+                    }),
+                );
 
                 dialog.present();
             }
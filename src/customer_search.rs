@@ -0,0 +1,245 @@
+use crate::customer::Customer;
+
+pub const DEFAULT_MAX_DISTANCE: usize = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SearchField {
+    #[default]
+    Full,
+    First,
+    Last,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MatchMode {
+    /// The query can appear anywhere in the name.
+    #[default]
+    Contains,
+    /// A name, or one of the words in it, has to start with the query.
+    Prefix,
+    /// Like `Contains`, but names within the given edit distance of the query match as well.
+    Fuzzy(usize),
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchOptions {
+    pub query: String,
+    pub field: SearchField,
+    pub mode: MatchMode,
+    pub ignore_case: bool,
+    pub min_items: Option<usize>,
+    pub max_items: Option<usize>,
+}
+
+impl SearchOptions {
+    /// Case insensitive `Contains` search over the full name, without an item filter.
+    pub fn new(query: &str) -> Self {
+        Self {
+            query: query.to_string(),
+            field: SearchField::default(),
+            mode: MatchMode::default(),
+            ignore_case: true,
+            min_items: None,
+            max_items: None,
+        }
+    }
+
+    /// Scores how well a customer matches, lower is better. Returns `None` when the customer does
+    /// not match at all.
+    pub fn score(&self, customer: &Customer) -> Option<usize> {
+        if self
+            .min_items
+            .is_some_and(|min_items| customer.no_items() < min_items)
+            || self
+                .max_items
+                .is_some_and(|max_items| customer.no_items() > max_items)
+        {
+            return None;
+        }
+
+        let query = self.fold_case(&self.query);
+        if query.is_empty() {
+            return Some(0);
+        }
+
+        let candidates = match self.field {
+            SearchField::Full => vec![
+                customer.full_name(),
+                customer.first_name().to_string(),
+                customer.last_name().to_string(),
+            ],
+            SearchField::First => vec![customer.first_name().to_string()],
+            SearchField::Last => vec![customer.last_name().to_string()],
+        };
+
+        candidates
+            .iter()
+            .filter_map(|candidate| self.score_candidate(&query, &self.fold_case(candidate)))
+            .min()
+    }
+
+    fn score_candidate(&self, query: &str, candidate: &str) -> Option<usize> {
+        if candidate == query {
+            return Some(0);
+        }
+
+        if candidate.starts_with(query) {
+            return Some(1);
+        }
+
+        match self.mode {
+            MatchMode::Prefix => None,
+            MatchMode::Contains => candidate.contains(query).then_some(2),
+            MatchMode::Fuzzy(max_distance) => {
+                if candidate.contains(query) {
+                    return Some(2);
+                }
+
+                // Also compare against the start of the name so misspelt partial names match
+                let prefix = candidate
+                    .chars()
+                    .take(query.chars().count())
+                    .collect::<String>();
+                let distance = edit_distance(query, candidate).min(edit_distance(query, &prefix));
+
+                (distance <= max_distance).then_some(3 + distance)
+            }
+        }
+    }
+
+    fn fold_case(&self, text: &str) -> String {
+        if self.ignore_case {
+            text.to_lowercase()
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SearchResult<'a> {
+    pub queue_no: usize,
+    pub customer_pos: usize,
+    pub customer: &'a Customer,
+    pub score: usize,
+}
+
+/// Levenshtein distance between two strings, counted in characters.
+pub fn edit_distance(text1: &str, text2: &str) -> usize {
+    let text2 = text2.chars().collect::<Vec<_>>();
+    let mut previous_row = (0..=text2.len()).collect::<Vec<_>>();
+
+    for (i, char1) in text1.chars().enumerate() {
+        let mut current_row = vec![i + 1; text2.len() + 1];
+
+        for (j, char2) in text2.iter().enumerate() {
+            let substitution_cost = if char1 == *char2 { 0 } else { 1 };
+
+            current_row[j + 1] = (previous_row[j] + substitution_cost)
+                .min(previous_row[j + 1] + 1)
+                .min(current_row[j] + 1);
+        }
+
+        previous_row = current_row;
+    }
+
+    previous_row[text2.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn jane() -> Customer {
+        Customer::new("Jane".to_string(), "Doe".to_string(), 3)
+    }
+
+    fn options(query: &str, mode: MatchMode) -> SearchOptions {
+        SearchOptions {
+            mode,
+            ..SearchOptions::new(query)
+        }
+    }
+
+    #[test]
+    fn counts_edits_in_characters() {
+        assert_eq!(edit_distance("", ""), 0);
+        assert_eq!(edit_distance("jane", "jane"), 0);
+        assert_eq!(edit_distance("jane", ""), 4);
+        assert_eq!(edit_distance("", "doe"), 3);
+        assert_eq!(edit_distance("jane", "jan"), 1);
+        assert_eq!(edit_distance("jane", "june"), 1);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("zoë", "zoe"), 1);
+    }
+
+    #[test]
+    fn ranks_exact_then_prefix_then_contains_then_fuzzy() {
+        let jane = jane();
+        let mode = MatchMode::Fuzzy(DEFAULT_MAX_DISTANCE);
+
+        let scores =
+            ["jane", "ja", "an", "jnae"].map(|query| options(query, mode).score(&jane).unwrap());
+
+        assert_eq!(scores, [0, 1, 2, 5]);
+    }
+
+    #[test]
+    fn folds_case_only_when_asked() {
+        let jane = jane();
+        let mut options = SearchOptions::new("JANE");
+
+        assert_eq!(options.score(&jane), Some(0));
+
+        options.ignore_case = false;
+        assert_eq!(options.score(&jane), None);
+
+        options.query = "Jane".to_string();
+        assert_eq!(options.score(&jane), Some(0));
+    }
+
+    #[test]
+    fn matches_the_start_of_names_in_prefix_mode() {
+        let jane = jane();
+
+        assert_eq!(options("do", MatchMode::Prefix).score(&jane), Some(1));
+        assert_eq!(options("jane d", MatchMode::Prefix).score(&jane), Some(1));
+        assert_eq!(options("an", MatchMode::Prefix).score(&jane), None);
+        assert_eq!(options("an", MatchMode::Contains).score(&jane), Some(2));
+    }
+
+    #[test]
+    fn matches_misspelt_names_in_fuzzy_mode() {
+        let jane = jane();
+
+        assert_eq!(options("jnae", MatchMode::Fuzzy(2)).score(&jane), Some(5));
+        assert_eq!(options("jnae", MatchMode::Fuzzy(1)).score(&jane), None);
+        assert_eq!(options("jnae", MatchMode::Contains).score(&jane), None);
+        // A misspelt start of the name is compared against the same number of characters
+        assert_eq!(options("jam", MatchMode::Fuzzy(1)).score(&jane), Some(4));
+    }
+
+    #[test]
+    fn filters_by_field_and_items() {
+        let jane = jane();
+        let mut options = SearchOptions::new("doe");
+
+        assert_eq!(options.score(&jane), Some(0));
+
+        options.field = SearchField::First;
+        assert_eq!(options.score(&jane), None);
+
+        options.field = SearchField::Last;
+        assert_eq!(options.score(&jane), Some(0));
+
+        options.min_items = Some(4);
+        assert_eq!(options.score(&jane), None);
+
+        options.min_items = Some(3);
+        options.max_items = Some(2);
+        assert_eq!(options.score(&jane), None);
+
+        options.max_items = Some(3);
+        assert_eq!(options.score(&jane), Some(0));
+    }
+}
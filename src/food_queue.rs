@@ -1,10 +1,17 @@
 use std::fmt::Display;
 
-use crate::customer::Customer;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone)]
+use crate::customer::{Customer, CustomerId};
+
+/// How many times a customer can be overtaken by higher priority customers before nobody else is
+/// allowed ahead of them.
+pub const MAX_OVERTAKES: usize = 3;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FoodQueue {
     id: usize,
+    #[serde(rename = "customers")]
     queue: Vec<Customer>,
     capacity: usize,
 }
@@ -16,6 +23,16 @@ pub enum FoodQueueError {
     CustomerNotFound,
 }
 
+impl Display for FoodQueueError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            FoodQueueError::Full => "Queue is full!",
+            FoodQueueError::Empty => "Queue is empty!",
+            FoodQueueError::CustomerNotFound => "Customer not found!",
+        })
+    }
+}
+
 impl Display for FoodQueue {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.write_fmt(format_args!(
@@ -37,7 +54,7 @@ impl FoodQueue {
     pub fn new(id: usize, capacity: usize) -> Self {
         Self {
             id,
-            queue: Vec::with_capacity(capacity),
+            queue: Vec::new(),
             capacity,
         }
     }
@@ -50,6 +67,11 @@ impl FoodQueue {
         self.id
     }
 
+    /// Used when loading, to fill in details saved after the queues themselves.
+    pub fn customers_mut(&mut self) -> &mut [Customer] {
+        self.queue.as_mut_slice()
+    }
+
     pub fn view_data(&self) -> &[Customer] {
         self.queue.as_slice()
     }
@@ -75,15 +97,95 @@ impl FoodQueue {
         Ok(self.queue.last().unwrap())
     }
 
-    pub fn remove_customer(&mut self, customer_pos: usize) -> Result<Customer, FoodQueueError> {
-        if self.is_empty() {
-            return Err(FoodQueueError::Empty);
+    /// Where a new customer would join the queue. They go ahead of everyone with a lower priority,
+    /// except those already overtaken `MAX_OVERTAKES` times.
+    pub fn priority_position(&self, customer: &Customer) -> usize {
+        let mut customer_pos = self.len();
+
+        while customer_pos > 0 {
+            let ahead = &self.queue[customer_pos - 1];
+
+            if ahead.priority() >= customer.priority() || ahead.overtaken() >= MAX_OVERTAKES {
+                break;
+            }
+
+            customer_pos -= 1;
+        }
+
+        customer_pos
+    }
+
+    /// Adds the customer at their priority position, counting an overtake against everyone they
+    /// went ahead of. Returns the position they joined at.
+    pub fn queue_customer(&mut self, customer: Customer) -> Result<usize, FoodQueueError> {
+        if self.is_full() {
+            return Err(FoodQueueError::Full);
+        }
+
+        let customer_pos = self.priority_position(&customer);
+        self.queue.insert(customer_pos, customer);
+
+        for overtaken in self.queue[customer_pos + 1..].iter_mut() {
+            overtaken.set_overtaken(overtaken.overtaken() + 1);
         }
 
+        Ok(customer_pos)
+    }
+
+    /// Reverses `queue_customer`, taking back the overtakes it counted.
+    pub fn withdraw_customer(&mut self, customer_pos: usize) -> Result<Customer, FoodQueueError> {
+        let customer = self.remove_customer(customer_pos)?;
+
+        for overtaken in self.queue[customer_pos..].iter_mut() {
+            overtaken.set_overtaken(overtaken.overtaken().saturating_sub(1));
+        }
+
+        Ok(customer)
+    }
+
+    pub fn insert_customer(
+        &mut self,
+        customer_pos: usize,
+        customer: Customer,
+    ) -> Result<&Customer, FoodQueueError> {
+        if self.is_full() {
+            return Err(FoodQueueError::Full);
+        }
+
+        if customer_pos > self.len() {
+            return Err(FoodQueueError::CustomerNotFound);
+        }
+
+        self.queue.insert(customer_pos, customer);
+        Ok(&self.queue[customer_pos])
+    }
+
+    pub fn remove_customer(&mut self, customer_pos: usize) -> Result<Customer, FoodQueueError> {
+        self.get_customer(customer_pos)?;
+
         Ok(self.queue.remove(customer_pos))
     }
 
+    /// Swaps the customer at the position for another, returning the one that was there.
+    pub fn replace_customer(
+        &mut self,
+        customer_pos: usize,
+        customer: Customer,
+    ) -> Result<Customer, FoodQueueError> {
+        self.get_customer(customer_pos)?;
+
+        Ok(std::mem::replace(&mut self.queue[customer_pos], customer))
+    }
+
+    pub fn position_of(&self, id: CustomerId) -> Option<usize> {
+        self.queue.iter().position(|customer| customer.id() == id)
+    }
+
     pub fn get_customer(&self, customer_pos: usize) -> Result<&Customer, FoodQueueError> {
+        if self.is_empty() {
+            return Err(FoodQueueError::Empty);
+        }
+
         Ok(self
             .queue
             .get(customer_pos)
@@ -4,10 +4,15 @@ use glib::Object;
 use gtk::gio;
 use gtk::glib;
 
-use std::cell::RefCell;
+use std::cell::{Cell, OnceCell, RefCell};
+use std::rc::Rc;
 
+use crate::customer::{Customer, CustomerId, LineItem, PriorityLevel};
+use crate::customer_search::{self, MatchMode, SearchOptions};
+use crate::food_queue::FoodQueueError;
 use crate::queue_box::QueueBox;
-use crate::shop::Shop;
+use crate::shop::{CustomerPlacement, PartialServePolicy, Shop, ShopError};
+use crate::shop_event::{ShopEvent, SubscriptionId};
 
 glib::wrapper! {
     pub struct GuiWindow(ObjectSubclass<imp::GuiWindow>)
@@ -17,15 +22,210 @@ glib::wrapper! {
 }
 
 impl GuiWindow {
-    pub fn new(shop: Shop) -> Self {
+    pub fn new(shop: Rc<RefCell<Shop>>) -> Self {
         let obj: Self = Object::builder().build();
         let imp = obj.imp();
 
-        imp.shop.replace(shop);
-        imp.initialize_queues();
+        imp.shop.set(shop).expect("the shop is only set once");
+        imp.rebuild_queues(&imp.shop().borrow());
+        obj.setup_actions();
+
+        imp.stock_banner
+            .connect_button_clicked(|banner| banner.set_revealed(false));
+
+        imp.search_popover.set_parent(&*imp.search_entry);
+        imp.search_entry
+            .connect_changed(glib::clone!(@weak obj => move |_| {
+                let imp = obj.imp();
+                imp.apply_search(&imp.shop().borrow());
+            }));
+
+        let subscription = imp.shop().borrow_mut().subscribe(
+            glib::clone!(@weak obj => move |shop, event| obj.imp().handle_shop_event(shop, event)),
+        );
+        imp.subscription.set(Some(subscription));
 
         obj
     }
+
+    fn setup_actions(&self) {
+        let undo_action = gio::SimpleAction::new("undo", None);
+        undo_action.connect_activate(glib::clone!(@weak self as window => move |_, _| {
+            let result = window.imp().shop().borrow_mut().undo().map(|_| ());
+            window.imp().handle_result(result);
+        }));
+        self.add_action(&undo_action);
+
+        let redo_action = gio::SimpleAction::new("redo", None);
+        redo_action.connect_activate(glib::clone!(@weak self as window => move |_, _| {
+            let result = window.imp().shop().borrow_mut().redo().map(|_| ());
+            window.imp().handle_result(result);
+        }));
+        self.add_action(&redo_action);
+
+        let move_action =
+            gio::SimpleAction::new("move-customer", Some(&<(u64, u32)>::static_variant_type()));
+        move_action.connect_activate(glib::clone!(@weak self as window => move |_, parameter| {
+            if let Some((id, to_queue_no)) =
+                parameter.and_then(|parameter| parameter.get::<(u64, u32)>())
+            {
+                window.imp().move_customer(CustomerId(id), to_queue_no as usize);
+            }
+        }));
+        self.add_action(&move_action);
+
+        let add_action = gio::SimpleAction::new("add-customer", None);
+        add_action.connect_activate(glib::clone!(@weak self as window => move |_, _| {
+            window.present_add_customer_dialog();
+        }));
+        self.add_action(&add_action);
+
+        let serve_action =
+            gio::SimpleAction::new("serve-customer", Some(&u32::static_variant_type()));
+        serve_action.connect_activate(glib::clone!(@weak self as window => move |_, parameter| {
+            if let Some(queue_no) = parameter.and_then(|parameter| parameter.get::<u32>()) {
+                window.imp().serve_customer(queue_no as usize);
+            }
+        }));
+        self.add_action(&serve_action);
+
+        let remove_action =
+            gio::SimpleAction::new("remove-customer", Some(&u64::static_variant_type()));
+        remove_action.connect_activate(glib::clone!(@weak self as window => move |_, parameter| {
+            if let Some(id) = parameter.and_then(|parameter| parameter.get::<u64>()) {
+                window.imp().remove_customer(CustomerId(id));
+            }
+        }));
+        self.add_action(&remove_action);
+
+        let policy = self.imp().shop().borrow().partial_serve_policy();
+        let policy_action = gio::SimpleAction::new_stateful(
+            "partial-serve-policy",
+            Some(&String::static_variant_type()),
+            &policy.key().to_variant(),
+        );
+        policy_action.connect_activate(
+            glib::clone!(@weak self as window => move |action, parameter| {
+                if let Some(policy) = parameter
+                    .and_then(|parameter| parameter.get::<String>())
+                    .and_then(|key| PartialServePolicy::from_key(&key))
+                {
+                    let result = window.imp().shop().borrow_mut().set_partial_serve_policy(policy);
+
+                    if result.is_ok() {
+                        action.set_state(&policy.key().to_variant());
+                    }
+
+                    window.imp().handle_result(result);
+                }
+            }),
+        );
+        self.add_action(&policy_action);
+
+        // The menu items share the action, so they show up as a set of radio buttons
+        let policy_menu = gio::Menu::new();
+        for policy in PartialServePolicy::ALL {
+            policy_menu.append(
+                Some(policy.name()),
+                Some(&format!("win.partial-serve-policy::{}", policy.key())),
+            );
+        }
+        self.imp().policy_button.set_menu_model(Some(&policy_menu));
+
+        self.imp().update_history_actions();
+    }
+
+    fn present_add_customer_dialog(&self) {
+        let first_name_entry = gtk::Entry::builder()
+            .placeholder_text("First name")
+            .activates_default(true)
+            .build();
+        let last_name_entry = gtk::Entry::builder()
+            .placeholder_text("Last name")
+            .activates_default(true)
+            .build();
+        let products = self.imp().shop().borrow().inventory().products().to_vec();
+        let quantity_buttons = products
+            .iter()
+            .enumerate()
+            .map(|(i, product)| {
+                let quantity_button =
+                    gtk::SpinButton::with_range(0.0, product.max_threshold() as f64, 1.0);
+                quantity_button.set_value(if i == 0 { 1.0 } else { 0.0 });
+
+                (product.name().to_string(), quantity_button)
+            })
+            .collect::<Vec<_>>();
+
+        let priority_names = PriorityLevel::ALL.map(|priority| priority.name());
+        let priority_dropdown = gtk::DropDown::from_strings(&priority_names);
+
+        let fields = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .spacing(6)
+            .build();
+        fields.append(&first_name_entry);
+        fields.append(&last_name_entry);
+        fields.append(
+            &gtk::Label::builder()
+                .label("Priority")
+                .halign(gtk::Align::Start)
+                .build(),
+        );
+        fields.append(&priority_dropdown);
+
+        for (name, quantity_button) in quantity_buttons.iter() {
+            fields.append(
+                &gtk::Label::builder()
+                    .label(name.as_str())
+                    .halign(gtk::Align::Start)
+                    .build(),
+            );
+            fields.append(quantity_button);
+        }
+
+        let dialog = adw::MessageDialog::builder()
+            .heading("Add Customer")
+            .extra_child(&fields)
+            .transient_for(self)
+            .build();
+
+        dialog.add_responses(&[("cancel", "Cancel"), ("add", "Add")]);
+        dialog.set_response_appearance("add", adw::ResponseAppearance::Suggested);
+        dialog.set_default_response(Some("add"));
+        dialog.set_close_response("cancel");
+
+        dialog.connect_response(
+            Some("add"),
+            glib::clone!(@weak self as window, @weak first_name_entry,
+            @weak last_name_entry, @weak priority_dropdown => move |_, _| {
+                let order = quantity_buttons
+                    .iter()
+                    .filter(|(_, quantity_button)| quantity_button.value_as_int() > 0)
+                    .map(|(name, quantity_button)| LineItem {
+                        product: name.clone(),
+                        quantity: quantity_button.value_as_int() as usize,
+                    })
+                    .collect();
+
+                let priority = PriorityLevel::ALL
+                    .get(priority_dropdown.selected() as usize)
+                    .copied()
+                    .unwrap_or_default();
+
+                window.imp().add_customer(
+                    Customer::with_order(
+                        first_name_entry.text().trim().to_string(),
+                        last_name_entry.text().trim().to_string(),
+                        order,
+                    )
+                    .with_priority(priority),
+                );
+            }),
+        );
+
+        dialog.present();
+    }
 }
 
 mod imp {
@@ -34,13 +234,32 @@ mod imp {
     #[derive(Default, Debug, gtk::CompositeTemplate)]
     #[template(file = "resources/gui_window.blp")]
     pub struct GuiWindow {
+        #[template_child]
+        pub toast_overlay: gtk::TemplateChild<adw::ToastOverlay>,
+
+        #[template_child]
+        pub stock_banner: gtk::TemplateChild<adw::Banner>,
+
         #[template_child]
         pub queue_container: gtk::TemplateChild<gtk::Box>,
 
         #[template_child]
         pub search_entry: gtk::TemplateChild<gtk::Entry>,
 
-        pub shop: RefCell<Shop>,
+        #[template_child]
+        pub waiting_label: gtk::TemplateChild<gtk::Label>,
+
+        #[template_child]
+        pub search_popover: gtk::TemplateChild<gtk::Popover>,
+
+        #[template_child]
+        pub search_results: gtk::TemplateChild<gtk::ListBox>,
+
+        #[template_child]
+        pub policy_button: gtk::TemplateChild<gtk::MenuButton>,
+
+        pub shop: OnceCell<Rc<RefCell<Shop>>>,
+        pub subscription: Cell<Option<SubscriptionId>>,
     }
 
     #[glib::object_subclass]
@@ -59,17 +278,259 @@ mod imp {
     }
 
     impl GuiWindow {
-        pub fn initialize_queues(&self) {
-            let shop = self.shop.borrow();
+        /// The shop is shared with the text interface, so changes made here outlive the window.
+        pub fn shop(&self) -> &RefCell<Shop> {
+            self.shop.get().expect("the shop is set in GuiWindow::new")
+        }
+
+        /// Called by the shop after every change. The shop is handed over directly since it is
+        /// still borrowed by whoever changed it.
+        pub fn handle_shop_event(&self, shop: &Shop, event: &ShopEvent) {
+            match event {
+                ShopEvent::CustomerAdded { queue_no }
+                | ShopEvent::CustomerRemoved { queue_no }
+                | ShopEvent::CustomerServed { queue_no }
+                | ShopEvent::SaleRefunded { queue_no } => self.update_queue(shop, *queue_no),
+                ShopEvent::WaitingListChanged => self.update_waiting_list(shop),
+                ShopEvent::QueuesReloaded => {
+                    self.rebuild_queues(shop);
+                    self.update_policy_action(shop);
+                }
+                ShopEvent::StockChanged { .. } => {
+                    // Restocking clears the warning, a serve raises it again with `StockLow`
+                    if shop.stock_warnings().is_empty() {
+                        self.stock_banner.set_revealed(false);
+                    }
+                }
+                ShopEvent::StockLow(warning) => {
+                    self.stock_banner.set_title(&warning.to_string());
+                    self.stock_banner.set_revealed(true);
+                }
+                // The add customer dialog reads the catalog whenever it is opened
+                ShopEvent::ProductsChanged => (),
+            }
+
+            self.apply_search(shop);
+        }
+
+        /// Highlights the customers matching the search entry and lists them in the popover.
+        pub fn apply_search(&self, shop: &Shop) {
+            let query = self.search_entry.text();
+            let mut matches = Vec::new();
+
+            while let Some(row) = self.search_results.first_child() {
+                self.search_results.remove(&row);
+            }
+
+            if !query.is_empty() {
+                let mut options = SearchOptions::new(&query);
+                options.mode = MatchMode::Fuzzy(customer_search::DEFAULT_MAX_DISTANCE);
+
+                for result in shop.search(&options) {
+                    matches.push(result.customer.id());
+
+                    self.search_results.append(
+                        &adw::ActionRow::builder()
+                            .title(result.customer.full_name())
+                            .subtitle(format!(
+                                "Queue {}, position {}, {} items",
+                                result.queue_no,
+                                result.customer_pos,
+                                result.customer.no_items()
+                            ))
+                            .build(),
+                    );
+                }
+
+                if self.search_results.first_child().is_none() {
+                    self.search_results.append(
+                        &adw::ActionRow::builder()
+                            .title("No customers found")
+                            .build(),
+                    );
+                }
+            }
 
-            for queue in shop.view_data() {
-                let queue_box = QueueBox::new(queue);
+            let mut child = self.queue_container.first_child();
+
+            while let Some(widget) = child {
+                if let Some(queue_box) = widget.downcast_ref::<QueueBox>() {
+                    queue_box.set_search_matches((!query.is_empty()).then_some(matches.as_slice()));
+                }
+
+                child = widget.next_sibling();
+            }
+
+            self.search_popover.set_visible(!query.is_empty());
+        }
+
+        pub fn rebuild_queues(&self, shop: &Shop) {
+            while let Some(child) = self.queue_container.first_child() {
+                self.queue_container.remove(&child);
+            }
+
+            let revenue = shop.ledger().by_queue();
+
+            for (queue_no, queue) in shop.view_data().iter().enumerate() {
+                let revenue = revenue.get(&queue_no).copied().unwrap_or_default();
+                let queue_box = QueueBox::new(queue_no, queue, revenue);
                 self.queue_container.append(&queue_box);
             }
+
+            self.update_waiting_list(shop);
+        }
+
+        pub fn update_queue(&self, shop: &Shop, queue_no: usize) {
+            let mut child = self.queue_container.first_child();
+            for _ in 0..queue_no {
+                child = child.and_then(|child| child.next_sibling());
+            }
+
+            if let (Some(queue_box), Some(queue)) = (
+                child.and_then(|child| child.downcast::<QueueBox>().ok()),
+                shop.view_data().get(queue_no),
+            ) {
+                let revenue = shop.ledger().by_queue();
+                queue_box.update(queue, revenue.get(&queue_no).copied().unwrap_or_default());
+            }
+        }
+
+        pub fn update_waiting_list(&self, shop: &Shop) {
+            let waiting_list = shop.waiting_list();
+
+            self.waiting_label.set_label(&if waiting_list.is_empty() {
+                "No customers are waiting".to_string()
+            } else {
+                format!(
+                    "Waiting: {}",
+                    waiting_list
+                        .iter()
+                        .map(|customer| customer.full_name())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )
+            });
+        }
+
+        pub fn add_customer(&self, customer: Customer) {
+            if customer.first_name().is_empty() {
+                self.show_toast("A first name is required!");
+                return;
+            }
+
+            if customer.order().is_empty() {
+                self.show_toast("An order needs at least one item!");
+                return;
+            }
+
+            let name = customer.full_name();
+            let result = self.shop().borrow_mut().add_customer(customer);
+
+            match result {
+                Ok(CustomerPlacement::Queue(queue_no)) => {
+                    self.show_toast(&format!("Added {name} to queue {queue_no}."))
+                }
+                Ok(CustomerPlacement::WaitingList(waiting_pos)) => self.show_toast(&format!(
+                    "All queues are full! Added {name} to the waiting list at {waiting_pos}."
+                )),
+                Err(error) => self.show_toast(&error.to_string()),
+            }
+
+            self.update_history_actions();
+        }
+
+        pub fn serve_customer(&self, queue_no: usize) {
+            let result = self.shop().borrow_mut().serve_customer(queue_no);
+
+            match result {
+                Ok(outcome) => self.show_toast(&outcome.to_string()),
+                Err(error) => self.show_toast(&error.to_string()),
+            }
+
+            self.update_history_actions();
+        }
+
+        /// Looks the customer up again since their position may have changed since the button
+        /// was made.
+        pub fn move_customer(&self, id: CustomerId, to_queue_no: usize) {
+            let mut shop = self.shop().borrow_mut();
+            let result = shop
+                .find_customer(id)
+                .ok_or(ShopError::QueueError(FoodQueueError::CustomerNotFound))
+                .and_then(|(queue_no, customer_pos)| {
+                    shop.move_customer(queue_no, customer_pos, to_queue_no)
+                        .map(|_| ())
+                });
+            drop(shop);
+
+            self.handle_result(result);
+        }
+
+        pub fn remove_customer(&self, id: CustomerId) {
+            let result = self.shop().borrow_mut().remove_customer_by_id(id);
+
+            match result {
+                Ok(customer) => self.show_toast(&format!(
+                    "Successfully removed customer {}",
+                    customer.first_name()
+                )),
+                Err(error) => self.show_toast(&error.to_string()),
+            }
+
+            self.update_history_actions();
+        }
+
+        /// Shows any error from an action that has nothing to report on success.
+        pub fn handle_result(&self, result: Result<(), ShopError>) {
+            if let Err(error) = result {
+                self.show_toast(&error.to_string());
+            }
+
+            self.update_history_actions();
+        }
+
+        pub fn show_toast(&self, message: &str) {
+            self.toast_overlay.add_toast(adw::Toast::new(message));
+        }
+
+        /// Loading a save can change the policy, so the menu is kept in step with the shop.
+        pub fn update_policy_action(&self, shop: &Shop) {
+            if let Some(action) = self
+                .obj()
+                .lookup_action("partial-serve-policy")
+                .and_then(|action| action.downcast::<gio::SimpleAction>().ok())
+            {
+                action.set_state(&shop.partial_serve_policy().key().to_variant());
+            }
+        }
+
+        pub fn update_history_actions(&self) {
+            let obj = self.obj();
+            let shop = self.shop().borrow();
+
+            for (name, enabled) in [
+                ("undo", shop.history().can_undo()),
+                ("redo", shop.history().can_redo()),
+            ] {
+                if let Some(action) = obj
+                    .lookup_action(name)
+                    .and_then(|action| action.downcast::<gio::SimpleAction>().ok())
+                {
+                    action.set_enabled(enabled);
+                }
+            }
         }
     }
 
-    impl ObjectImpl for GuiWindow {}
+    impl ObjectImpl for GuiWindow {
+        fn dispose(&self) {
+            self.search_popover.unparent();
+
+            if let Some(subscription) = self.subscription.take() {
+                self.shop().borrow_mut().unsubscribe(subscription);
+            }
+        }
+    }
 
     impl WidgetImpl for GuiWindow {}
 
@@ -0,0 +1,110 @@
+use std::collections::VecDeque;
+
+use chrono::{DateTime, Local};
+
+use crate::{
+    customer::{Customer, LineItem},
+    shop::PartialServePolicy,
+    stock_log::StockReason,
+};
+
+pub const HISTORY_LIMIT: usize = 50;
+
+/// A customer moved from the waiting list into the queue that had room for them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Promotion {
+    /// Where they were on the waiting list.
+    pub waiting_pos: usize,
+    /// Where they joined the queue.
+    pub customer_pos: usize,
+}
+
+/// A reversible shop operation, holding everything needed to undo it exactly.
+#[derive(Debug, Clone)]
+pub enum HistoryEntry {
+    AddCustomer {
+        queue_no: usize,
+        customer_pos: usize,
+        customer: Customer,
+        /// Whether the queue was picked by the assignment strategy rather than asked for.
+        assigned: bool,
+    },
+    RemoveCustomer {
+        queue_no: usize,
+        customer_pos: usize,
+        customer: Customer,
+        promoted: Option<Promotion>,
+    },
+    ServeCustomer {
+        queue_no: usize,
+        customer: Customer,
+        served: Vec<LineItem>,
+        /// Redoing the serve records the sale at the time it first happened.
+        served_at: DateTime<Local>,
+        policy: PartialServePolicy,
+        /// Whether the customer stayed at the front of the queue with the rest of their order.
+        kept: bool,
+        promoted: Option<Promotion>,
+    },
+    WaitCustomer {
+        customer: Customer,
+    },
+    SetStock {
+        product: String,
+        previous_stock: usize,
+        stock: usize,
+        reason: StockReason,
+    },
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    undo_stack: VecDeque<HistoryEntry>,
+    redo_stack: Vec<HistoryEntry>,
+}
+
+impl History {
+    /// Records a new operation. Anything that could be redone is dropped since it no longer
+    /// follows from the current state.
+    pub fn push(&mut self, entry: HistoryEntry) {
+        self.redo_stack.clear();
+        self.undo_stack.push_back(entry);
+
+        if self.undo_stack.len() > HISTORY_LIMIT {
+            self.undo_stack.pop_front();
+        }
+    }
+
+    pub fn next_undo(&self) -> Option<&HistoryEntry> {
+        self.undo_stack.back()
+    }
+
+    pub fn next_redo(&self) -> Option<&HistoryEntry> {
+        self.redo_stack.last()
+    }
+
+    pub fn mark_undone(&mut self) {
+        if let Some(entry) = self.undo_stack.pop_back() {
+            self.redo_stack.push(entry);
+        }
+    }
+
+    pub fn mark_redone(&mut self) {
+        if let Some(entry) = self.redo_stack.pop() {
+            self.undo_stack.push_back(entry);
+        }
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo_stack.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo_stack.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+    }
+}
@@ -0,0 +1,282 @@
+use std::{collections::BTreeMap, fmt::Display};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    customer::LineItem,
+    shop::{ITEM_PRICE, STOCK_LOW_THRESHOLD, STOCK_MAX_THRESHOLD},
+};
+
+/// The product sold before the shop had a catalog. Customers given only a number of items order
+/// this, and it is always part of the catalog.
+pub const DEFAULT_PRODUCT: &str = "Item";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Product {
+    name: String,
+    stock: usize,
+    price: usize,
+    low_threshold: usize,
+    max_threshold: usize,
+}
+
+#[derive(Debug)]
+pub enum InventoryError {
+    ProductNotFound(String),
+    DuplicateProduct(String),
+    InvalidName,
+    InvalidThresholds,
+    ProductShort {
+        product: String,
+        needed: usize,
+        available: usize,
+    },
+}
+
+impl Display for InventoryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InventoryError::ProductNotFound(product) => {
+                f.write_fmt(format_args!("Product \"{product}\" not found!"))
+            }
+            InventoryError::DuplicateProduct(product) => {
+                f.write_fmt(format_args!("Product \"{product}\" already exists!"))
+            }
+            InventoryError::InvalidName => {
+                f.write_str("Product names cannot be empty or contain commas or colons!")
+            }
+            InventoryError::InvalidThresholds => {
+                f.write_str("The low threshold must be below the maximum!")
+            }
+            InventoryError::ProductShort {
+                product,
+                needed,
+                available,
+            } => f.write_fmt(format_args!(
+                "Not enough {product} in stock, {needed} needed but only {available} left!"
+            )),
+        }
+    }
+}
+
+impl Display for Product {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "{}\n{}\n{}\n{}\n{}",
+            self.name, self.stock, self.price, self.low_threshold, self.max_threshold
+        ))
+    }
+}
+
+impl Product {
+    pub fn new(
+        name: String,
+        price: usize,
+        low_threshold: usize,
+        max_threshold: usize,
+    ) -> Result<Self, InventoryError> {
+        // Commas and colons separate line items in the save file
+        if name.trim().is_empty() || name.contains([',', ':']) {
+            return Err(InventoryError::InvalidName);
+        }
+
+        if low_threshold >= max_threshold {
+            return Err(InventoryError::InvalidThresholds);
+        }
+
+        Ok(Self {
+            name,
+            stock: 0,
+            price,
+            low_threshold,
+            max_threshold,
+        })
+    }
+
+    /// Used when loading a product from a save file.
+    pub fn with_stock(mut self, stock: usize) -> Self {
+        self.stock = stock;
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    pub fn stock(&self) -> usize {
+        self.stock
+    }
+
+    pub fn price(&self) -> usize {
+        self.price
+    }
+
+    pub fn low_threshold(&self) -> usize {
+        self.low_threshold
+    }
+
+    pub fn max_threshold(&self) -> usize {
+        self.max_threshold
+    }
+
+    pub fn is_low(&self) -> bool {
+        self.stock <= self.low_threshold
+    }
+}
+
+/// Every product the shop sells, in the order they were added.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Inventory {
+    products: Vec<Product>,
+}
+
+impl Default for Inventory {
+    fn default() -> Self {
+        Self {
+            products: vec![Product {
+                name: DEFAULT_PRODUCT.to_string(),
+                stock: 0,
+                price: ITEM_PRICE,
+                low_threshold: STOCK_LOW_THRESHOLD,
+                max_threshold: STOCK_MAX_THRESHOLD,
+            }],
+        }
+    }
+}
+
+impl Inventory {
+    /// Builds a catalog from saved products, adding the default product if it is missing.
+    pub fn from_products(products: Vec<Product>) -> Result<Self, InventoryError> {
+        let mut inventory = Self::default();
+
+        for product in products {
+            if product.name == DEFAULT_PRODUCT {
+                inventory.products[0] = product;
+            } else {
+                inventory.add(product)?;
+            }
+        }
+
+        Ok(inventory)
+    }
+
+    pub fn products(&self) -> &[Product] {
+        self.products.as_slice()
+    }
+
+    pub fn get(&self, name: &str) -> Result<&Product, InventoryError> {
+        self.products
+            .iter()
+            .find(|product| product.name == name)
+            .ok_or_else(|| InventoryError::ProductNotFound(name.to_string()))
+    }
+
+    pub fn add(&mut self, product: Product) -> Result<(), InventoryError> {
+        if self.get(&product.name).is_ok() {
+            return Err(InventoryError::DuplicateProduct(product.name));
+        }
+
+        self.products.push(product);
+        Ok(())
+    }
+
+    pub fn set_stock(&mut self, name: &str, stock: usize) -> Result<(), InventoryError> {
+        self.products
+            .iter_mut()
+            .find(|product| product.name == name)
+            .ok_or_else(|| InventoryError::ProductNotFound(name.to_string()))?
+            .stock = stock;
+
+        Ok(())
+    }
+
+    /// Checks that every product in the order is sold here.
+    pub fn check_order(&self, order: &[LineItem]) -> Result<(), InventoryError> {
+        for line_item in order {
+            self.get(&line_item.product)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the stock each product in the order is left with once the order is taken out,
+    /// without changing anything. Fails on the first product that cannot cover the order.
+    pub fn remaining_after(
+        &self,
+        order: &[LineItem],
+    ) -> Result<BTreeMap<String, usize>, InventoryError> {
+        let mut needed = BTreeMap::<&str, usize>::new();
+
+        for line_item in order {
+            *needed.entry(line_item.product.as_str()).or_default() += line_item.quantity;
+        }
+
+        let mut remaining = BTreeMap::new();
+
+        for (name, needed) in needed {
+            let product = self.get(name)?;
+            let stock =
+                product
+                    .stock
+                    .checked_sub(needed)
+                    .ok_or_else(|| InventoryError::ProductShort {
+                        product: name.to_string(),
+                        needed,
+                        available: product.stock,
+                    })?;
+
+            remaining.insert(name.to_string(), stock);
+        }
+
+        Ok(remaining)
+    }
+
+    /// Splits the order into what the current stock can cover and what it cannot, filling line
+    /// items in order.
+    pub fn split_order(
+        &self,
+        order: &[LineItem],
+    ) -> Result<(Vec<LineItem>, Vec<LineItem>), InventoryError> {
+        let mut available = BTreeMap::<&str, usize>::new();
+        let mut served = Vec::new();
+        let mut shortfall = Vec::new();
+
+        for line_item in order {
+            let product = self.get(&line_item.product)?;
+            let stock = available
+                .entry(line_item.product.as_str())
+                .or_insert(product.stock);
+
+            let quantity = line_item.quantity.min(*stock);
+            *stock -= quantity;
+
+            if quantity > 0 {
+                served.push(LineItem {
+                    product: line_item.product.clone(),
+                    quantity,
+                });
+            }
+
+            if quantity < line_item.quantity {
+                shortfall.push(LineItem {
+                    product: line_item.product.clone(),
+                    quantity: line_item.quantity - quantity,
+                });
+            }
+        }
+
+        Ok((served, shortfall))
+    }
+
+    /// Prices the order at the current product prices.
+    pub fn price_of(&self, order: &[LineItem]) -> Result<usize, InventoryError> {
+        let mut price = 0;
+
+        for line_item in order {
+            price += self.get(&line_item.product)?.price * line_item.quantity;
+        }
+
+        Ok(price)
+    }
+}
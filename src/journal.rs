@@ -0,0 +1,145 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    customer::Customer, inventory::Product, shop::PartialServePolicy, stock_log::StockChange,
+};
+
+pub const DEFAULT_JOURNAL_PATH: &str = "./program_state.journal";
+
+/// A single mutation of the shop. Entries are written before the change is applied, so replaying
+/// them on top of the snapshot they follow rebuilds the exact state.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "operation", rename_all = "snake_case")]
+pub enum JournalEntry {
+    Snapshot {
+        path: PathBuf,
+    },
+    /// Starts a journal that has no snapshot to follow with the queues and stock the shop was
+    /// opened with, since those can change between runs.
+    Opened {
+        queue_layout: Vec<usize>,
+        stock: usize,
+    },
+    AddCustomer {
+        queue_no: usize,
+        customer: Customer,
+    },
+    InsertCustomer {
+        queue_no: usize,
+        customer_pos: usize,
+        customer: Customer,
+    },
+    RemoveCustomer {
+        queue_no: usize,
+        customer_pos: usize,
+    },
+    /// Adds the customer at their priority position, see `FoodQueue::queue_customer`.
+    QueueCustomer {
+        queue_no: usize,
+        customer: Customer,
+    },
+    WithdrawCustomer {
+        queue_no: usize,
+        customer_pos: usize,
+    },
+    ServeCustomer {
+        queue_no: usize,
+        served_at: DateTime<Local>,
+        policy: PartialServePolicy,
+        operator: String,
+    },
+    RefundSale,
+    SetPartialServePolicy {
+        policy: PartialServePolicy,
+    },
+    ChangeStock {
+        change: StockChange,
+    },
+    AddProduct {
+        product: Product,
+    },
+    JoinWaitingList {
+        customer: Customer,
+    },
+    LeaveWaitingList,
+    PromoteCustomer {
+        queue_no: usize,
+    },
+    DemoteCustomer {
+        queue_no: usize,
+        customer_pos: usize,
+        waiting_pos: usize,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct Journal {
+    path: PathBuf,
+}
+
+impl Journal {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    pub fn path(&self) -> &Path {
+        self.path.as_path()
+    }
+
+    pub fn append(&self, entry: &JournalEntry) -> io::Result<()> {
+        self.append_all(std::slice::from_ref(entry))
+    }
+
+    /// Appends several entries with a single write.
+    pub fn append_all(&self, entries: &[JournalEntry]) -> io::Result<()> {
+        let mut lines = String::new();
+
+        for entry in entries {
+            lines.push_str(&serde_json::to_string(entry)?);
+            lines.push('\n');
+        }
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+
+        file.write_all(lines.as_bytes())?;
+        file.sync_data()
+    }
+
+    /// Reads back every complete entry. A crash can only tear the last line, so reading stops at
+    /// the first line that does not parse.
+    pub fn read_entries(&self) -> io::Result<Vec<JournalEntry>> {
+        let file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(error) => return Err(error),
+        };
+
+        let mut entries = Vec::new();
+
+        for line in BufReader::new(file).lines() {
+            match serde_json::from_str(&line?) {
+                Ok(entry) => entries.push(entry),
+                Err(_) => break,
+            }
+        }
+
+        Ok(entries)
+    }
+
+    /// Starts the journal over. Later entries will be replayed on top of the given starting
+    /// point, which is either a `Snapshot` or the layout the shop was `Opened` with.
+    pub fn checkpoint(&self, start: &JournalEntry) -> io::Result<()> {
+        File::create(&self.path)?;
+        self.append(start)
+    }
+}
@@ -0,0 +1,110 @@
+use std::{collections::BTreeMap, fmt::Display};
+
+use chrono::{DateTime, Local, NaiveDate};
+use serde::{Deserialize, Serialize};
+
+use crate::customer::{Customer, LineItem};
+
+/// A single serve, priced when it happened. `no_items` and `price` only cover what was served,
+/// anything the stock could not cover is kept in `shortfall`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Sale {
+    pub customer: Customer,
+    pub queue_no: usize,
+    pub no_items: usize,
+    pub price: usize,
+    pub timestamp: DateTime<Local>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub shortfall: Vec<LineItem>,
+}
+
+impl Display for Sale {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "{}\n{}\n{}\n{}\n{}",
+            self.customer,
+            self.queue_no,
+            self.no_items,
+            self.price,
+            self.timestamp.to_rfc3339()
+        ))
+    }
+}
+
+/// Totals of a group of sales.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Revenue {
+    pub no_sales: usize,
+    pub no_items: usize,
+    pub revenue: usize,
+    /// Items ordered that could not be served.
+    pub items_short: usize,
+}
+
+impl Revenue {
+    fn add(&mut self, sale: &Sale) {
+        self.no_sales += 1;
+        self.no_items += sale.no_items;
+        self.revenue += sale.price;
+        self.items_short += sale
+            .shortfall
+            .iter()
+            .map(|line_item| line_item.quantity)
+            .sum::<usize>();
+    }
+}
+
+/// Every sale the shop has made, oldest first.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Ledger {
+    sales: Vec<Sale>,
+}
+
+impl Ledger {
+    pub fn sales(&self) -> &[Sale] {
+        self.sales.as_slice()
+    }
+
+    pub fn record(&mut self, sale: Sale) {
+        self.sales.push(sale);
+    }
+
+    /// Takes back the most recent sale, used when a serve is undone.
+    pub fn refund_last(&mut self) -> Option<Sale> {
+        self.sales.pop()
+    }
+
+    pub fn total(&self) -> Revenue {
+        let mut total = Revenue::default();
+
+        for sale in self.sales.iter() {
+            total.add(sale);
+        }
+
+        total
+    }
+
+    pub fn by_queue(&self) -> BTreeMap<usize, Revenue> {
+        self.group_by(|sale| sale.queue_no)
+    }
+
+    pub fn by_day(&self) -> BTreeMap<NaiveDate, Revenue> {
+        self.group_by(|sale| sale.timestamp.date_naive())
+    }
+
+    /// Customers sharing a name are kept apart by their ID.
+    pub fn by_customer(&self) -> BTreeMap<String, Revenue> {
+        self.group_by(|sale| format!("{} ({})", sale.customer.full_name(), sale.customer.id()))
+    }
+
+    fn group_by<K: Ord>(&self, key: impl Fn(&Sale) -> K) -> BTreeMap<K, Revenue> {
+        let mut groups = BTreeMap::<K, Revenue>::new();
+
+        for sale in self.sales.iter() {
+            groups.entry(key(sale)).or_default().add(sale);
+        }
+
+        groups
+    }
+}
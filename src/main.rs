@@ -1,14 +1,124 @@
-use shop::Shop;
+use std::{
+    env,
+    fs::File,
+    io::{self, BufReader},
+    path::Path,
+    process,
+};
+
+use journal::Journal;
+use shop::{SaveFormat, Shop};
 use text_interface::TextInterface;
 
+mod cli;
 mod customer;
 mod customer_button;
+mod customer_search;
 mod food_queue;
 mod gui_window;
+mod history;
+mod inventory;
+mod journal;
+mod ledger;
 mod queue_box;
+mod queue_strategy;
+mod script;
 mod shop;
+mod shop_event;
+mod stock_log;
+mod storage;
 mod text_interface;
 
 fn main() {
-    TextInterface::new(Shop::new(&[2, 3, 5])).run();
+    let options = match cli::parse_args(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(error) => {
+            eprintln!("{error}\n\n{}", cli::USAGE);
+            process::exit(2);
+        }
+    };
+
+    if options.help {
+        println!("{}", cli::USAGE);
+        return;
+    }
+
+    let mut shop = Shop::new(&options.layout).with_stock(options.initial_stock.unwrap_or(0));
+    shop.set_operator(
+        options
+            .operator
+            .clone()
+            .or_else(|| env::var("USER").ok())
+            .unwrap_or_else(|| shop::DEFAULT_OPERATOR.to_string()),
+    );
+
+    if let Some(script_path) = options.script_path.as_deref() {
+        process::exit(run_script(shop, script_path, options.load_path.as_deref()));
+    }
+
+    shop.set_journal(Some(Journal::new(journal::DEFAULT_JOURNAL_PATH)));
+
+    match run_interactive(TextInterface::new(shop), &options) {
+        Ok(exit_code) => process::exit(exit_code),
+        Err(error) => {
+            eprintln!("The text interface stopped unexpectedly: {error}");
+            process::exit(1);
+        }
+    }
+}
+
+/// Returns the exit code of the program.
+fn run_interactive(mut text_interface: TextInterface, options: &cli::Options) -> io::Result<i32> {
+    // Loading starts the journal over, so there is nothing to recover the changes onto
+    match options.load_path.as_deref() {
+        Some(path) => {
+            text_interface.warn_unsaved_changes(path)?;
+
+            if !text_interface.load_file(path)? {
+                return Ok(1);
+            }
+        }
+        None => text_interface.recover_journal()?,
+    }
+
+    if options.gui {
+        text_interface.gui()?;
+    } else {
+        text_interface.run()?;
+    }
+
+    Ok(0)
+}
+
+/// Scripts are not journaled since nobody is around to answer the recovery prompt afterwards.
+/// Returns the exit code of the program.
+fn run_script(mut shop: Shop, script_path: &Path, load_path: Option<&Path>) -> i32 {
+    if let Some(load_path) = load_path {
+        let result = File::open(load_path)
+            .map_err(shop::LoadError::IOError)
+            .and_then(|mut file| shop.load(&mut file, SaveFormat::from_path(load_path)));
+
+        if let Err(error) = result {
+            eprintln!("Failed to load {}: {error}", load_path.display());
+            return 1;
+        }
+    }
+
+    let result = if script_path == Path::new("-") {
+        script::run_script(&mut shop, io::stdin().lock(), io::stdout().lock())
+    } else {
+        match File::open(script_path) {
+            Ok(file) => script::run_script(&mut shop, BufReader::new(file), io::stdout().lock()),
+            Err(error) => Err(error),
+        }
+    };
+
+    match result {
+        Ok(true) => 0,
+        Ok(false) => 1,
+        Err(error) => {
+            eprintln!("Failed to run {}: {error}", script_path.display());
+            1
+        }
+    }
 }
@@ -1,9 +1,12 @@
+use gtk::gdk;
 use gtk::glib;
 use gtk::prelude::*;
 use gtk::subclass::prelude::*;
 
+use crate::customer::{Customer, CustomerId};
 use crate::customer_button::CustomerButton;
 use crate::food_queue::FoodQueue;
+use crate::ledger::Revenue;
 
 glib::wrapper! {
     pub struct QueueBox(ObjectSubclass<imp::QueueBox>)
@@ -12,19 +15,81 @@ glib::wrapper! {
 }
 
 impl QueueBox {
-    pub fn new(queue: &FoodQueue) -> Self {
+    pub fn new(queue_no: usize, queue: &FoodQueue, revenue: Revenue) -> Self {
         let queue_box: QueueBox = glib::Object::builder().build();
+        queue_box
+            .imp()
+            .serve_button
+            .set_action_target_value(Some(&(queue_no as u32).to_variant()));
+        queue_box.update(queue, revenue);
+
+        // Customers dragged from another queue are handed to the window to be moved in the shop
+        let drop_target = gtk::DropTarget::new(String::static_type(), gdk::DragAction::MOVE);
+        drop_target.connect_drop(
+            glib::clone!(@weak queue_box => @default-return false, move |_, value, _, _| {
+                let source = value
+                    .get::<String>()
+                    .ok()
+                    .and_then(|source| CustomerButton::parse_drag_source(&source));
+
+                match source {
+                    Some(id) => {
+                        let parameter = (id.0, queue_no as u32);
+
+                        queue_box
+                            .activate_action("win.move-customer", Some(&parameter.to_variant()))
+                            .is_ok()
+                    }
+                    None => false,
+                }
+            }),
+        );
+        queue_box.add_controller(drop_target);
+
+        queue_box
+    }
+
+    /// Shows the customers in the queue along with what it has served, taken from the ledger.
+    pub fn update(&self, queue: &FoodQueue, revenue: Revenue) {
+        let imp = self.imp();
         let empty_spaces = queue.capacity() - queue.len();
 
+        while let Some(child) = imp.customer_container.first_child() {
+            imp.customer_container.remove(&child);
+        }
+
         for customer in queue.view_data() {
-            queue_box.append(&CustomerButton::new(Some(customer.clone())));
+            imp.customer_container
+                .append(&CustomerButton::new(Some(customer.clone())));
         }
 
         for _ in 0..empty_spaces {
-            queue_box.append(&CustomerButton::new(None));
+            imp.customer_container.append(&CustomerButton::new(None));
         }
 
-        queue_box
+        imp.serve_button.set_sensitive(!queue.is_empty());
+        imp.income_label.set_label(&format!(
+            "{} items served\nIncome: {}",
+            revenue.no_items, revenue.revenue
+        ));
+    }
+
+    /// Applies search styling to the customer buttons, `matches` holds the IDs of the customers
+    /// that matched. Passing `None` clears the styling.
+    pub fn set_search_matches(&self, matches: Option<&[CustomerId]>) {
+        let mut child = self.imp().customer_container.first_child();
+
+        while let Some(widget) = child {
+            if let Some(button) = widget.downcast_ref::<CustomerButton>() {
+                let id = button.imp().customer.borrow().as_ref().map(Customer::id);
+
+                button.set_search_match(
+                    matches.map(|matches| id.is_some_and(|id| matches.contains(&id))),
+                );
+            }
+
+            child = widget.next_sibling();
+        }
     }
 }
 
@@ -33,7 +98,16 @@ mod imp {
 
     #[derive(Default, Debug, gtk::CompositeTemplate)]
     #[template(file = "resources/queue_box.blp")]
-    pub struct QueueBox;
+    pub struct QueueBox {
+        #[template_child]
+        pub customer_container: gtk::TemplateChild<gtk::Box>,
+
+        #[template_child]
+        pub serve_button: gtk::TemplateChild<gtk::Button>,
+
+        #[template_child]
+        pub income_label: gtk::TemplateChild<gtk::Label>,
+    }
 
     #[glib::object_subclass]
     impl ObjectSubclass for QueueBox {
@@ -0,0 +1,263 @@
+use std::fmt::Debug;
+
+use crate::{customer::Customer, food_queue::FoodQueue};
+
+pub const EXPRESS_LANE_MAX_ITEMS: usize = 5;
+
+/// Decides which queue a new customer joins when they do not ask for a specific one.
+pub trait QueueAssignmentStrategy: Debug {
+    fn name(&self) -> &'static str;
+
+    /// Returns the position of the chosen queue, or `None` when no queue can take the customer.
+    /// Choosing a queue does not commit to it, the customer may still fail to join.
+    fn select_queue(&self, queues: &[FoodQueue], customer: &Customer) -> Option<usize>;
+
+    /// Called by the shop once a customer has joined the queue this strategy selected.
+    fn customer_assigned(&mut self, _queue_no: usize) {}
+
+    /// Called by the shop when undo takes a customer back out of the queue this strategy
+    /// assigned them to.
+    fn assignment_undone(&mut self, _queue_no: usize) {}
+
+    fn clone_box(&self) -> Box<dyn QueueAssignmentStrategy>;
+}
+
+impl Clone for Box<dyn QueueAssignmentStrategy> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+impl Default for Box<dyn QueueAssignmentStrategy> {
+    fn default() -> Self {
+        Box::new(ShortestQueue)
+    }
+}
+
+/// Every strategy that ships with the program, in the order they are offered to the user.
+pub fn built_in_strategies() -> Vec<Box<dyn QueueAssignmentStrategy>> {
+    vec![
+        Box::new(ShortestQueue),
+        Box::new(RoundRobin::default()),
+        Box::new(FewestItems),
+        Box::new(ExpressLane::default()),
+    ]
+}
+
+fn shortest_queue<'a>(queues: impl Iterator<Item = (usize, &'a FoodQueue)>) -> Option<usize> {
+    queues
+        .filter(|(_, queue)| !queue.is_full())
+        .min_by(|(_, queue1), (_, queue2)| queue1.len().cmp(&queue2.len()))
+        .map(|(queue_no, _)| queue_no)
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ShortestQueue;
+
+impl QueueAssignmentStrategy for ShortestQueue {
+    fn name(&self) -> &'static str {
+        "Shortest queue"
+    }
+
+    fn select_queue(&self, queues: &[FoodQueue], _customer: &Customer) -> Option<usize> {
+        shortest_queue(queues.iter().enumerate())
+    }
+
+    fn clone_box(&self) -> Box<dyn QueueAssignmentStrategy> {
+        Box::new(self.clone())
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RoundRobin {
+    next_queue: usize,
+}
+
+impl QueueAssignmentStrategy for RoundRobin {
+    fn name(&self) -> &'static str {
+        "Round robin"
+    }
+
+    fn select_queue(&self, queues: &[FoodQueue], _customer: &Customer) -> Option<usize> {
+        (0..queues.len())
+            .map(|offset| (self.next_queue + offset) % queues.len())
+            .find(|&queue_no| !queues[queue_no].is_full())
+    }
+
+    fn customer_assigned(&mut self, queue_no: usize) {
+        self.next_queue = queue_no + 1;
+    }
+
+    // The queues skipped on the way to `queue_no` were full and undo puts them back as they were,
+    // so starting from `queue_no` picks the same queues as the cursor it had before
+    fn assignment_undone(&mut self, queue_no: usize) {
+        self.next_queue = queue_no;
+    }
+
+    fn clone_box(&self) -> Box<dyn QueueAssignmentStrategy> {
+        Box::new(self.clone())
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct FewestItems;
+
+impl QueueAssignmentStrategy for FewestItems {
+    fn name(&self) -> &'static str {
+        "Fewest items waiting"
+    }
+
+    fn select_queue(&self, queues: &[FoodQueue], _customer: &Customer) -> Option<usize> {
+        queues
+            .iter()
+            .enumerate()
+            .filter(|(_, queue)| !queue.is_full())
+            .min_by_key(|(_, queue)| {
+                queue
+                    .view_data()
+                    .iter()
+                    .map(|customer| customer.no_items())
+                    .sum::<usize>()
+            })
+            .map(|(queue_no, _)| queue_no)
+    }
+
+    fn clone_box(&self) -> Box<dyn QueueAssignmentStrategy> {
+        Box::new(self.clone())
+    }
+}
+
+/// Keeps one queue for small orders. Customers with few items go there while it has room and fall
+/// back to the shortest queue otherwise. Everyone else only joins it when every other queue is
+/// full, rather than being sent to the waiting list while it has room.
+#[derive(Debug, Clone)]
+pub struct ExpressLane {
+    pub queue_no: usize,
+    pub max_items: usize,
+}
+
+impl Default for ExpressLane {
+    fn default() -> Self {
+        Self {
+            queue_no: 0,
+            max_items: EXPRESS_LANE_MAX_ITEMS,
+        }
+    }
+}
+
+impl QueueAssignmentStrategy for ExpressLane {
+    fn name(&self) -> &'static str {
+        "Express lane"
+    }
+
+    fn select_queue(&self, queues: &[FoodQueue], customer: &Customer) -> Option<usize> {
+        if customer.no_items() <= self.max_items {
+            match queues.get(self.queue_no) {
+                Some(queue) if !queue.is_full() => Some(self.queue_no),
+                _ => shortest_queue(queues.iter().enumerate()),
+            }
+        } else {
+            shortest_queue(
+                queues
+                    .iter()
+                    .enumerate()
+                    .filter(|(queue_no, _)| *queue_no != self.queue_no),
+            )
+            .or_else(|| shortest_queue(queues.iter().enumerate()))
+        }
+    }
+
+    fn clone_box(&self) -> Box<dyn QueueAssignmentStrategy> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Queues of the given capacities, each holding one customer per entry of `orders` with that
+    /// many items.
+    fn queues(layout: &[(usize, &[usize])]) -> Vec<FoodQueue> {
+        layout
+            .iter()
+            .enumerate()
+            .map(|(queue_no, (capacity, orders))| {
+                let mut queue = FoodQueue::new(queue_no, *capacity);
+
+                for no_items in orders.iter() {
+                    queue.add_customer(customer(*no_items)).unwrap();
+                }
+
+                queue
+            })
+            .collect()
+    }
+
+    fn customer(no_items: usize) -> Customer {
+        Customer::new("Jane".to_string(), "Doe".to_string(), no_items)
+    }
+
+    #[test]
+    fn round_robin_moves_on_only_once_assigned() {
+        let queues = queues(&[(2, &[]), (2, &[]), (2, &[])]);
+        let mut strategy = RoundRobin::default();
+
+        assert_eq!(strategy.select_queue(&queues, &customer(1)), Some(0));
+        assert_eq!(strategy.select_queue(&queues, &customer(1)), Some(0));
+
+        strategy.customer_assigned(0);
+        assert_eq!(strategy.select_queue(&queues, &customer(1)), Some(1));
+
+        strategy.customer_assigned(2);
+        assert_eq!(strategy.select_queue(&queues, &customer(1)), Some(0));
+
+        strategy.assignment_undone(2);
+        assert_eq!(strategy.select_queue(&queues, &customer(1)), Some(2));
+    }
+
+    #[test]
+    fn round_robin_skips_full_queues() {
+        let queues = queues(&[(1, &[1]), (1, &[1]), (2, &[])]);
+        let strategy = RoundRobin::default();
+
+        assert_eq!(strategy.select_queue(&queues, &customer(1)), Some(2));
+
+        let queues = self::queues(&[(1, &[1]), (1, &[1])]);
+        assert_eq!(strategy.select_queue(&queues, &customer(1)), None);
+    }
+
+    #[test]
+    fn fewest_items_counts_items_rather_than_customers() {
+        let queues = queues(&[(3, &[1, 1]), (3, &[9]), (1, &[1])]);
+
+        assert_eq!(FewestItems.select_queue(&queues, &customer(1)), Some(0));
+
+        let queues = self::queues(&[(2, &[1, 1]), (2, &[9])]);
+        assert_eq!(FewestItems.select_queue(&queues, &customer(1)), Some(1));
+    }
+
+    #[test]
+    fn express_lane_takes_small_orders() {
+        let queues = queues(&[(3, &[1, 1]), (3, &[])]);
+        let strategy = ExpressLane::default();
+
+        assert_eq!(strategy.select_queue(&queues, &customer(5)), Some(0));
+        assert_eq!(strategy.select_queue(&queues, &customer(6)), Some(1));
+
+        // A full express lane sends small orders to the shortest queue
+        let queues = self::queues(&[(1, &[1]), (3, &[9]), (3, &[])]);
+        assert_eq!(strategy.select_queue(&queues, &customer(1)), Some(2));
+    }
+
+    #[test]
+    fn express_lane_takes_large_orders_once_the_others_are_full() {
+        let queues = queues(&[(3, &[]), (1, &[9]), (1, &[9])]);
+        let strategy = ExpressLane::default();
+
+        assert_eq!(strategy.select_queue(&queues, &customer(9)), Some(0));
+
+        let queues = self::queues(&[(1, &[1]), (1, &[9])]);
+        assert_eq!(strategy.select_queue(&queues, &customer(9)), None);
+    }
+}
@@ -0,0 +1,469 @@
+use std::{
+    fmt::Display,
+    fs::File,
+    io::{self, BufRead, Write},
+};
+
+use serde_json::{json, Map, Value};
+
+use crate::{
+    customer::{self, Customer, PriorityLevel},
+    customer_search::SearchOptions,
+    inventory::Product,
+    queue_strategy,
+    shop::{CustomerPlacement, LoadError, PartialServePolicy, SaveFormat, Shop, ShopError},
+    stock_log::{self, StockReason},
+    storage,
+};
+
+#[derive(Debug)]
+pub enum ScriptError {
+    UnknownCommand(String),
+    UnsupportedCommand(String),
+    MissingArgument(&'static str),
+    InvalidArgument(&'static str, String),
+    ShopError(ShopError),
+    LoadError(LoadError),
+    IOError(io::Error),
+}
+
+impl Display for ScriptError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScriptError::UnknownCommand(command) => {
+                f.write_fmt(format_args!("Unknown command \"{command}\"!"))
+            }
+            ScriptError::UnsupportedCommand(command) => {
+                f.write_fmt(format_args!("{command} cannot be used in a script!"))
+            }
+            ScriptError::MissingArgument(name) => {
+                f.write_fmt(format_args!("Missing argument: {name}!"))
+            }
+            ScriptError::InvalidArgument(name, value) => {
+                f.write_fmt(format_args!("Invalid {name}: \"{value}\"!"))
+            }
+            ScriptError::ShopError(error) => error.fmt(f),
+            ScriptError::LoadError(error) => error.fmt(f),
+            ScriptError::IOError(error) => error.fmt(f),
+        }
+    }
+}
+
+/// Runs text interface commands from a script, one per line, with their arguments given inline
+/// instead of being prompted for, e.g. `ACQ Jane Doe 4`. Blank lines and lines starting with `#`
+/// are skipped.
+///
+/// Every command writes a single line of JSON to the output holding the script line number, the
+/// command, a `status` of either `ok` or `error`, and any data the command produced. Returns
+/// whether every command succeeded.
+pub fn run_script(
+    shop: &mut Shop,
+    input: impl BufRead,
+    mut output: impl Write,
+) -> io::Result<bool> {
+    let mut succeeded = true;
+
+    for (line_no, line) in input.lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let mut words = line.split_whitespace();
+        let command = words.next().unwrap_or_default().to_uppercase();
+        let args = words.collect::<Vec<_>>();
+
+        let mut result = Map::new();
+        result.insert("line".to_string(), json!(line_no + 1));
+        result.insert("command".to_string(), json!(command));
+
+        match execute(shop, &command, &args) {
+            Ok(data) => {
+                result.insert("status".to_string(), json!("ok"));
+
+                if let Value::Object(data) = data {
+                    result.extend(data);
+                }
+            }
+            Err(error) => {
+                succeeded = false;
+                result.insert("status".to_string(), json!("error"));
+                result.insert("error".to_string(), json!(error.to_string()));
+            }
+        }
+
+        writeln!(output, "{}", Value::Object(result))?;
+
+        if command == "EXT" {
+            break;
+        }
+    }
+
+    output.flush()?;
+
+    Ok(succeeded)
+}
+
+fn execute(shop: &mut Shop, command: &str, args: &[&str]) -> Result<Value, ScriptError> {
+    match command {
+        "VFQ" => Ok(json!({
+            "queues": shop.view_data(),
+            "waiting_list": shop.waiting_list(),
+        })),
+        "VEQ" => Ok(json!({
+            "queues": shop
+                .view_data()
+                .iter()
+                .enumerate()
+                .filter(|(_, queue)| !queue.is_full())
+                .map(|(queue_no, queue)| json!({
+                    "queue_no": queue_no,
+                    "free": queue.capacity() - queue.len(),
+                }))
+                .collect::<Vec<_>>(),
+        })),
+        "ACQ" => {
+            // The order is either a number of items or a list like `Item:2,Drink:1`
+            let order = text_arg(args, 2, "order")?;
+            let customer = Customer::with_order(
+                text_arg(args, 0, "first name")?.to_string(),
+                text_arg(args, 1, "last name")?.to_string(),
+                customer::parse_order(order)
+                    .ok_or_else(|| ScriptError::InvalidArgument("order", order.to_string()))?,
+            );
+
+            // The queue number and priority are both optional, so `ACQ Jane Doe 2 staff` and
+            // `ACQ Jane Doe 2 1 staff` both work
+            let (queue_arg, priority_arg) = match args.get(3).copied().map(PriorityLevel::from_name)
+            {
+                Some(Some(_)) => (None, args.get(3)),
+                _ => (args.get(3), args.get(4)),
+            };
+            let customer = match priority_arg {
+                Some(name) => {
+                    customer.with_priority(PriorityLevel::from_name(name).ok_or_else(|| {
+                        ScriptError::InvalidArgument("priority", name.to_string())
+                    })?)
+                }
+                None => customer,
+            };
+
+            let placement = match queue_arg {
+                Some(_) => {
+                    let queue_no = number_arg(args, 3, "queue number", 0, usize::MAX)?;
+                    shop.add_customer_to(queue_no, customer)
+                        .map(|_| CustomerPlacement::Queue(queue_no))
+                }
+                None => shop.add_customer(customer),
+            }
+            .map_err(ScriptError::ShopError)?;
+
+            Ok(match placement {
+                CustomerPlacement::Queue(queue_no) => json!({ "queue_no": queue_no }),
+                CustomerPlacement::WaitingList(waiting_pos) => {
+                    json!({ "waiting_pos": waiting_pos })
+                }
+            })
+        }
+        "RCQ" => {
+            let queue_no = number_arg(args, 0, "queue number", 0, usize::MAX)?;
+            let customer_pos = number_arg(args, 1, "customer position", 0, usize::MAX)?;
+
+            let customer = shop
+                .remove_customer(queue_no, customer_pos)
+                .map_err(ScriptError::ShopError)?;
+
+            Ok(json!({ "customer": customer }))
+        }
+        "PCQ" => {
+            let queue_no = number_arg(args, 0, "queue number", 0, usize::MAX)?;
+            let outcome = shop
+                .serve_customer(queue_no)
+                .map_err(ScriptError::ShopError)?;
+
+            Ok(json!({
+                "customer": outcome.customer,
+                "served": outcome.served,
+                "shortfall": outcome.shortfall,
+                "kept": outcome.kept,
+                "products": shop.inventory().products(),
+                "stock_warnings": shop.stock_warnings(),
+            }))
+        }
+        "VCS" => Ok(json!({ "customers": shop.get_sorted_customers() })),
+        "SCS" => {
+            let results = shop
+                .search(&SearchOptions::new(&args.join(" ")))
+                .iter()
+                .map(|result| {
+                    json!({
+                        "queue_no": result.queue_no,
+                        "customer_pos": result.customer_pos,
+                        "customer": result.customer,
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            Ok(json!({ "results": results }))
+        }
+        "SPD" => {
+            let path = storage::resolve_save_path(text_arg(args, 0, "save path")?);
+            storage::write_save(&path, shop).map_err(ScriptError::IOError)?;
+
+            Ok(json!({ "path": path }))
+        }
+        "LPD" => {
+            let path = storage::resolve_save_path(text_arg(args, 0, "save path")?);
+            let mut save_file = File::open(&path)
+                .map_err(|error| ScriptError::LoadError(LoadError::IOError(error)))?;
+            shop.load(&mut save_file, SaveFormat::from_path(&path))
+                .map_err(ScriptError::LoadError)?;
+
+            Ok(json!({ "path": path }))
+        }
+        "STK" => Ok(json!({ "products": shop.inventory().products() })),
+        "AFS" => {
+            let name = text_arg(args, 0, "product name")?;
+            let product = shop
+                .inventory()
+                .get(name)
+                .map_err(|error| ScriptError::ShopError(ShopError::InventoryError(error)))?;
+            let stock = product.stock();
+
+            let no_items = number_arg(
+                args,
+                1,
+                "number of items",
+                0,
+                product.max_threshold() - stock,
+            )?;
+            shop.set_stock(name, stock + no_items, StockReason::Delivery)
+                .map_err(ScriptError::ShopError)?;
+
+            Ok(json!({ "product": name, "stock": stock + no_items }))
+        }
+        "ADJ" => {
+            let name = text_arg(args, 0, "product name")?;
+            let product = shop
+                .inventory()
+                .get(name)
+                .map_err(|error| ScriptError::ShopError(ShopError::InventoryError(error)))?;
+            let stock = product.stock();
+
+            // Deliveries go through AFS and sales through PCQ
+            let reason = text_arg(args, 1, "reason")?;
+            let reason = match StockReason::from_name(reason) {
+                Some(reason @ (StockReason::Correction | StockReason::Wastage)) => reason,
+                _ => return Err(ScriptError::InvalidArgument("reason", reason.to_string())),
+            };
+
+            let new_stock = match reason {
+                StockReason::Wastage => {
+                    stock - number_arg(args, 2, "number of items wasted", 0, stock)?
+                }
+                _ => number_arg(args, 2, "counted stock", 0, product.max_threshold())?,
+            };
+
+            shop.set_stock(name, new_stock, reason)
+                .map_err(ScriptError::ShopError)?;
+
+            Ok(json!({ "product": name, "reason": reason, "stock": new_stock }))
+        }
+        "VSL" => Ok(json!({ "changes": shop.stock_log().changes() })),
+        "ESL" => {
+            let path = args
+                .first()
+                .copied()
+                .unwrap_or(stock_log::DEFAULT_EXPORT_PATH);
+            File::create(path)
+                .and_then(|file| shop.stock_log().export_csv(file))
+                .map_err(ScriptError::IOError)?;
+
+            Ok(json!({ "path": path }))
+        }
+        "RSR" => Ok(json!({ "products": shop.stock_log().reconcile(shop.inventory()) })),
+        "APR" => {
+            let product = Product::new(
+                text_arg(args, 0, "product name")?.to_string(),
+                number_arg(args, 1, "price", 0, usize::MAX)?,
+                number_arg(args, 2, "low stock threshold", 0, usize::MAX)?,
+                number_arg(args, 3, "maximum stock", 1, usize::MAX)?,
+            )
+            .map_err(|error| ScriptError::ShopError(ShopError::InventoryError(error)))?;
+
+            shop.add_product(product.clone())
+                .map_err(ScriptError::ShopError)?;
+
+            Ok(json!({ "product": product }))
+        }
+        "UND" => {
+            shop.undo().map_err(ScriptError::ShopError)?;
+            Ok(json!({}))
+        }
+        "RED" => {
+            shop.redo().map_err(ScriptError::ShopError)?;
+            Ok(json!({}))
+        }
+        "QAS" => {
+            let mut strategies = queue_strategy::built_in_strategies();
+            let strategy_no = number_arg(args, 0, "strategy number", 0, strategies.len() - 1)?;
+
+            shop.set_strategy(strategies.swap_remove(strategy_no));
+
+            Ok(json!({ "strategy": shop.strategy().name() }))
+        }
+        "RPQ" => Ok(json!({
+            "revenue": shop.ledger().by_queue(),
+            "total": shop.ledger().total(),
+        })),
+        "RPD" => Ok(json!({
+            "revenue": shop.ledger().by_day(),
+            "total": shop.ledger().total(),
+        })),
+        "RPC" => Ok(json!({
+            "revenue": shop.ledger().by_customer(),
+            "total": shop.ledger().total(),
+        })),
+        "PSP" => {
+            let policy_no = number_arg(
+                args,
+                0,
+                "policy number",
+                0,
+                PartialServePolicy::ALL.len() - 1,
+            )?;
+            let policy = PartialServePolicy::ALL[policy_no];
+
+            shop.set_partial_serve_policy(policy)
+                .map_err(ScriptError::ShopError)?;
+
+            Ok(json!({ "policy": policy }))
+        }
+        "EXT" => Ok(json!({})),
+        "LSD" | "RBK" | "GUI" | "HHH" => Err(ScriptError::UnsupportedCommand(command.to_string())),
+        _ => Err(ScriptError::UnknownCommand(command.to_string())),
+    }
+}
+
+fn text_arg<'a>(
+    args: &[&'a str],
+    index: usize,
+    name: &'static str,
+) -> Result<&'a str, ScriptError> {
+    args.get(index)
+        .copied()
+        .ok_or(ScriptError::MissingArgument(name))
+}
+
+fn number_arg(
+    args: &[&str],
+    index: usize,
+    name: &'static str,
+    start: usize,
+    end: usize,
+) -> Result<usize, ScriptError> {
+    let value = text_arg(args, index, name)?;
+
+    match value.parse() {
+        Ok(number) if (start..=end).contains(&number) => Ok(number),
+        _ => Err(ScriptError::InvalidArgument(name, value.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::inventory::DEFAULT_PRODUCT;
+
+    /// Runs `script` and returns whether it succeeded along with the JSON line of each command.
+    fn run(shop: &mut Shop, script: &str) -> (bool, Vec<Value>) {
+        let mut output = Vec::new();
+        let succeeded = run_script(shop, Cursor::new(script.as_bytes()), &mut output).unwrap();
+
+        let results = String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+
+        (succeeded, results)
+    }
+
+    #[test]
+    fn writes_a_line_per_command() {
+        let mut shop = Shop::new(&[2, 3]).with_stock(10);
+        let (succeeded, results) = run(&mut shop, "# Opening\n\nACQ Jane Doe 3\nPCQ 0\n");
+
+        assert!(succeeded);
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0]["line"], 3);
+        assert_eq!(results[0]["command"], "ACQ");
+        assert_eq!(results[0]["status"], "ok");
+        assert_eq!(results[0]["queue_no"], 0);
+        assert_eq!(results[1]["line"], 4);
+        assert_eq!(results[1]["served"][0]["quantity"], 3);
+        assert_eq!(shop.inventory().get(DEFAULT_PRODUCT).unwrap().stock(), 7);
+    }
+
+    #[test]
+    fn tells_the_queue_number_and_priority_apart() {
+        let mut shop = Shop::new(&[2, 3]).with_stock(10);
+        let (succeeded, results) = run(
+            &mut shop,
+            "ACQ Jane Doe 2 staff\nACQ John Doe 2 1\nACQ Anna Doe 2 1 priority\n",
+        );
+
+        assert!(succeeded);
+        assert_eq!(results[0]["queue_no"], 0);
+        assert_eq!(results[1]["queue_no"], 1);
+        assert_eq!(results[2]["queue_no"], 1);
+
+        let queues = shop.view_data();
+        assert_eq!(queues[0].view_data()[0].priority(), PriorityLevel::Staff);
+        assert_eq!(queues[1].view_data()[0].priority(), PriorityLevel::Priority);
+        assert_eq!(queues[1].view_data()[1].priority(), PriorityLevel::Regular);
+    }
+
+    #[test]
+    fn stops_at_ext() {
+        let mut shop = Shop::new(&[2]).with_stock(10);
+        let (succeeded, results) = run(&mut shop, "EXT\nACQ Jane Doe 3\n");
+
+        assert!(succeeded);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0]["command"], "EXT");
+        assert!(shop.view_data()[0].is_empty());
+    }
+
+    #[test]
+    fn reports_errors_and_carries_on() {
+        let mut shop = Shop::new(&[2]).with_stock(10);
+        let (succeeded, results) = run(&mut shop, "FOO\nRCQ 0\nPCQ 0\nAFS Item 0\n");
+
+        assert!(!succeeded);
+        assert_eq!(results.len(), 4);
+        assert_eq!(results[0]["status"], "error");
+        assert_eq!(results[0]["error"], "Unknown command \"FOO\"!");
+        assert_eq!(results[1]["error"], "Missing argument: customer position!");
+        assert_eq!(results[2]["status"], "error");
+        assert_eq!(results[3]["status"], "ok");
+        assert_eq!(results[3]["stock"], 10);
+    }
+
+    #[test]
+    fn adds_to_the_stock_of_a_product() {
+        let mut shop = Shop::new(&[2]).with_stock(10);
+        let (succeeded, results) = run(&mut shop, "AFS Item 5\nAFS Item\nAFS 5\n");
+
+        assert!(!succeeded);
+        assert_eq!(results[0]["product"], DEFAULT_PRODUCT);
+        assert_eq!(results[0]["stock"], 15);
+        assert_eq!(results[1]["error"], "Missing argument: number of items!");
+        assert_eq!(results[2]["status"], "error");
+        assert_eq!(shop.inventory().get(DEFAULT_PRODUCT).unwrap().stock(), 15);
+    }
+}
@@ -1,172 +1,2530 @@
 use std::{
+    collections::{HashSet, VecDeque},
     fmt::Display,
     fs::File,
     io::{self, Read, Write},
+    iter::Peekable,
+    path::Path,
+    str::Lines,
 };
 
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
 use crate::{
-    customer::Customer,
+    customer::{self, Customer, CustomerId, LineItem, PriorityLevel},
+    customer_search::{SearchOptions, SearchResult},
     food_queue::{FoodQueue, FoodQueueError},
+    history::{History, HistoryEntry, Promotion},
+    inventory::{Inventory, InventoryError, Product, DEFAULT_PRODUCT},
+    journal::{Journal, JournalEntry},
+    ledger::{Ledger, Sale},
+    queue_strategy::{QueueAssignmentStrategy, ShortestQueue},
+    shop_event::{EventBus, ShopEvent, SubscriptionId},
+    stock_log::{StockChange, StockLog, StockReason},
 };
 
 pub const STOCK_LOW_THRESHOLD: usize = 10;
 pub const STOCK_MAX_THRESHOLD: usize = 50;
 pub const ITEM_PRICE: usize = 500;
+pub const SAVE_FORMAT_VERSION: u32 = 2;
+pub const DEFAULT_OPERATOR: &str = "unknown";
 
 #[derive(Default, Debug, Clone)]
 pub struct Shop {
     queues: Vec<FoodQueue>,
-    stock: usize,
+    inventory: Inventory,
+    journal: Option<Journal>,
+    history: History,
+    strategy: Box<dyn QueueAssignmentStrategy>,
+    waiting_list: VecDeque<Customer>,
+    ledger: Ledger,
+    partial_serve_policy: PartialServePolicy,
+    stock_log: StockLog,
+    /// Who is running the shop, recorded with every stock change.
+    operator: String,
+    events: EventBus,
+    /// Set while a transaction is running, see [`Shop::transaction`].
+    staged: Option<Staged>,
+}
+
+/// Journal entries and events held back by a transaction until every step of it has succeeded.
+#[derive(Default, Debug, Clone)]
+struct Staged {
+    entries: Vec<JournalEntry>,
+    events: Vec<ShopEvent>,
+}
+
+/// Where a newly added customer ended up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CustomerPlacement {
+    Queue(usize),
+    WaitingList(usize),
+}
+
+/// What to do when the stock cannot cover a customer's whole order.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PartialServePolicy {
+    /// Serve nothing and leave the customer at the front of the queue.
+    #[default]
+    Refuse,
+    /// Serve what is in stock and keep the customer at the front with the rest of their order.
+    KeepRemaining,
+    /// Serve what is in stock and let the customer leave without the rest.
+    RemoveCustomer,
+}
+
+impl PartialServePolicy {
+    pub const ALL: [PartialServePolicy; 3] = [
+        PartialServePolicy::Refuse,
+        PartialServePolicy::KeepRemaining,
+        PartialServePolicy::RemoveCustomer,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PartialServePolicy::Refuse => "Refuse",
+            PartialServePolicy::KeepRemaining => "Serve partially and keep the customer",
+            PartialServePolicy::RemoveCustomer => "Serve partially and remove the customer",
+        }
+    }
+
+    /// A short name for save files and actions, the same one used in JSON.
+    pub fn key(&self) -> &'static str {
+        match self {
+            PartialServePolicy::Refuse => "refuse",
+            PartialServePolicy::KeepRemaining => "keep_remaining",
+            PartialServePolicy::RemoveCustomer => "remove_customer",
+        }
+    }
+
+    pub fn from_key(key: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|policy| policy.key().eq_ignore_ascii_case(key.trim()))
+    }
+}
+
+/// What happened to the customer at the front of a queue when they were served. `customer` holds
+/// their whole order as it was before the serve.
+#[derive(Debug, Clone, Serialize)]
+pub struct ServeOutcome {
+    pub customer: Customer,
+    pub served: Vec<LineItem>,
+    pub shortfall: Vec<LineItem>,
+    /// Whether the customer is still at the front of the queue waiting for the shortfall.
+    pub kept: bool,
+}
+
+impl ServeOutcome {
+    pub fn is_partial(&self) -> bool {
+        !self.shortfall.is_empty()
+    }
+}
+
+impl Display for ServeOutcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if !self.is_partial() {
+            return f.write_fmt(format_args!(
+                "Customer {} was served {}!",
+                self.customer.first_name(),
+                customer::summarize_order(&self.served)
+            ));
+        }
+
+        f.write_fmt(format_args!(
+            "Customer {} was partially served {}, {} could not be served! {}",
+            self.customer.first_name(),
+            customer::summarize_order(&self.served),
+            customer::summarize_order(&self.shortfall),
+            if self.kept {
+                "They are still at the front of the queue."
+            } else {
+                "They have left the queue."
+            }
+        ))
+    }
+}
+
+/// Raised when a serve leaves a product at or below its low threshold.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StockWarning {
+    pub product: String,
+    pub stock: usize,
+    /// How much of the product every customer in the queues and the waiting list wants.
+    pub queued_items: usize,
+    /// Stock to add so that everyone queued can be served without dropping to the threshold.
+    pub reorder: usize,
+}
+
+impl Display for StockWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "{} stock is low, only {} left! Queued customers want {}, consider adding {} to stock.",
+            self.product, self.stock, self.queued_items, self.reorder
+        ))
+    }
 }
 
 #[derive(Debug)]
 pub enum ShopError {
-    Full,
     QueueNotFound,
-    StockInsufficient,
     QueueError(FoodQueueError),
+    InventoryError(InventoryError),
+    JournalError(io::Error),
+    NothingToUndo,
+    NothingToRedo,
+    NothingToRefund,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoadField {
+    Stock,
+    QueueCount,
+    QueueId,
+    Capacity,
+    Length,
+    FirstName,
+    LastName,
+    Order,
+    WaitingCount,
+    SaleCount,
+    SaleQueue,
+    SaleItems,
+    SalePrice,
+    SaleTime,
+    ProductCount,
+    ProductName,
+    ProductStock,
+    ProductPrice,
+    LowThreshold,
+    MaxThreshold,
+    ShortfallCount,
+    ShortfallSale,
+    Shortfall,
+    ChangeCount,
+    ChangeProduct,
+    ChangeReason,
+    ChangeQuantity,
+    ChangeStock,
+    ChangeTime,
+    ChangeOperator,
+    PriorityCount,
+    Priority,
+    Overtaken,
+    CustomerIdCount,
+    CustomerId,
+    PartialServePolicy,
+}
+
+#[derive(Debug)]
+pub enum LoadErrorKind {
+    Missing,
+    InvalidNumber(String),
+    ExceedsCapacity(usize),
+    DuplicateQueueId,
+    ExceedsStockMax(usize),
+    InvalidTimestamp(String),
+    InvalidOrder(String),
+    InventoryError(InventoryError),
+    SaleNotFound(usize),
+    InvalidReason(String),
+    InvalidPriority(String),
+    DuplicateCustomerId(CustomerId),
+    InvalidPolicy(String),
+    CustomerCountMismatch(usize),
+    CustomerIdTooLarge(CustomerId),
+}
+
+#[derive(Debug)]
+pub enum LoadError {
+    IOError(io::Error),
+    JsonError(serde_json::Error),
+    UnsupportedVersion(u32),
+    InvalidField {
+        line: usize,
+        field: LoadField,
+        kind: LoadErrorKind,
+    },
+    InvalidValue {
+        field: LoadField,
+        kind: LoadErrorKind,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SaveFormat {
+    Text,
+    Json,
+}
+
+impl SaveFormat {
+    /// Picks the format from the file extension, falling back to the line based text format.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some(extension) if extension.eq_ignore_ascii_case("json") => SaveFormat::Json,
+            _ => SaveFormat::Text,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct SaveData {
+    version: u32,
+    inventory: Inventory,
+    queues: Vec<FoodQueue>,
+    waiting_list: VecDeque<Customer>,
+    ledger: Ledger,
+    stock_log: StockLog,
+    partial_serve_policy: PartialServePolicy,
+}
+
+impl Display for ShopError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ShopError::QueueNotFound => f.write_str("Queue not found!"),
+            ShopError::QueueError(error) => error.fmt(f),
+            ShopError::InventoryError(error) => error.fmt(f),
+            ShopError::JournalError(error) => f.write_fmt(format_args!(
+                "Failed to record the change, it was not applied! {error}"
+            )),
+            ShopError::NothingToUndo => f.write_str("Nothing to undo!"),
+            ShopError::NothingToRedo => f.write_str("Nothing to redo!"),
+            ShopError::NothingToRefund => f.write_str("There are no sales to refund!"),
+        }
+    }
+}
+
+impl Display for LoadField {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            LoadField::Stock => "stock",
+            LoadField::QueueCount => "queue count",
+            LoadField::QueueId => "queue id",
+            LoadField::Capacity => "queue capacity",
+            LoadField::Length => "queue length",
+            LoadField::FirstName => "first name",
+            LoadField::LastName => "last name",
+            LoadField::Order => "order",
+            LoadField::WaitingCount => "waiting list length",
+            LoadField::SaleCount => "sale count",
+            LoadField::SaleQueue => "sale queue",
+            LoadField::SaleItems => "sale item count",
+            LoadField::SalePrice => "sale price",
+            LoadField::SaleTime => "sale time",
+            LoadField::ProductCount => "product count",
+            LoadField::ProductName => "product name",
+            LoadField::ProductStock => "product stock",
+            LoadField::ProductPrice => "product price",
+            LoadField::LowThreshold => "low stock threshold",
+            LoadField::MaxThreshold => "maximum stock threshold",
+            LoadField::ShortfallCount => "shortfall count",
+            LoadField::ShortfallSale => "shortfall sale",
+            LoadField::Shortfall => "shortfall",
+            LoadField::ChangeCount => "stock change count",
+            LoadField::ChangeProduct => "stock change product",
+            LoadField::ChangeReason => "stock change reason",
+            LoadField::ChangeQuantity => "stock change quantity",
+            LoadField::ChangeStock => "stock change stock",
+            LoadField::ChangeTime => "stock change time",
+            LoadField::ChangeOperator => "stock change operator",
+            LoadField::PriorityCount => "customer priority count",
+            LoadField::Priority => "customer priority",
+            LoadField::Overtaken => "customer overtake count",
+            LoadField::CustomerIdCount => "customer id count",
+            LoadField::CustomerId => "customer id",
+            LoadField::PartialServePolicy => "partial serve policy",
+        })
+    }
+}
+
+impl LoadErrorKind {
+    fn describe(&self, field: &LoadField) -> String {
+        match self {
+            LoadErrorKind::Missing => format!("{field} is missing!"),
+            LoadErrorKind::InvalidNumber(value) => {
+                format!("{field} must be a valid number, found \"{value}\"!")
+            }
+            LoadErrorKind::ExceedsCapacity(capacity) => {
+                format!("{field} exceeds the queue capacity of {capacity}!")
+            }
+            LoadErrorKind::DuplicateQueueId => format!("{field} is already used by another queue!"),
+            LoadErrorKind::ExceedsStockMax(max_threshold) => {
+                format!("{field} exceeds the maximum of {max_threshold}!")
+            }
+            LoadErrorKind::InvalidTimestamp(value) => {
+                format!("{field} must be a valid timestamp, found \"{value}\"!")
+            }
+            LoadErrorKind::InvalidOrder(value) => format!(
+                "{field} must be a number or a list like Item:2,Drink:1, found \"{value}\"!"
+            ),
+            LoadErrorKind::InventoryError(error) => format!("{field} is invalid! {error}"),
+            LoadErrorKind::SaleNotFound(sale_no) => {
+                format!("{field} refers to sale {sale_no}, which does not exist!")
+            }
+            LoadErrorKind::InvalidReason(value) => {
+                format!(
+                    "{field} must be opening, delivery, correction, wastage or sale, \
+                     found \"{value}\"!"
+                )
+            }
+            LoadErrorKind::InvalidPriority(value) => {
+                format!("{field} must be regular, priority or staff, found \"{value}\"!")
+            }
+            LoadErrorKind::DuplicateCustomerId(id) => {
+                format!("{field} {id} is used by more than one customer!")
+            }
+            LoadErrorKind::CustomerIdTooLarge(id) => format!("{field} {id} is too large!"),
+            LoadErrorKind::CustomerCountMismatch(no_customers) => {
+                format!("{field} must match the {no_customers} customers before it!")
+            }
+            LoadErrorKind::InvalidPolicy(value) => format!(
+                "{field} must be refuse, keep_remaining or remove_customer, found \"{value}\"!"
+            ),
+        }
+    }
+}
+
+impl Display for LoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LoadError::IOError(error) => error.fmt(f),
+            LoadError::JsonError(error) => {
+                f.write_fmt(format_args!("Save file is not valid JSON: {error}"))
+            }
+            LoadError::UnsupportedVersion(version) => f.write_fmt(format_args!(
+                "Save file version {version} is not supported by this program!"
+            )),
+            LoadError::InvalidField { line, field, kind } => {
+                f.write_fmt(format_args!("Line {line}: {}", kind.describe(field)))
+            }
+            LoadError::InvalidValue { field, kind } => f.write_str(&kind.describe(field)),
+        }
+    }
+}
+
+/// Walks the lines of a save file while keeping track of the current line number.
+struct LineReader<'a> {
+    lines: Peekable<Lines<'a>>,
+    line_no: usize,
+}
+
+impl<'a> LineReader<'a> {
+    fn new(data: &'a str) -> Self {
+        Self {
+            lines: data.lines().peekable(),
+            line_no: 0,
+        }
+    }
+
+    fn has_next(&mut self) -> bool {
+        self.lines.peek().is_some()
+    }
+
+    fn error(&self, field: LoadField, kind: LoadErrorKind) -> LoadError {
+        LoadError::InvalidField {
+            line: self.line_no,
+            field,
+            kind,
+        }
+    }
+
+    fn next_str(&mut self, field: LoadField) -> Result<&'a str, LoadError> {
+        self.line_no += 1;
+
+        self.lines
+            .next()
+            .ok_or_else(|| self.error(field, LoadErrorKind::Missing))
+    }
+
+    fn next_usize(&mut self, field: LoadField) -> Result<usize, LoadError> {
+        let value = self.next_str(field)?;
+
+        value
+            .trim()
+            .parse()
+            .map_err(|_| self.error(field, LoadErrorKind::InvalidNumber(value.to_string())))
+    }
+
+    fn next_isize(&mut self, field: LoadField) -> Result<isize, LoadError> {
+        let value = self.next_str(field)?;
+
+        value
+            .trim()
+            .parse()
+            .map_err(|_| self.error(field, LoadErrorKind::InvalidNumber(value.to_string())))
+    }
+
+    fn next_timestamp(&mut self, field: LoadField) -> Result<DateTime<Local>, LoadError> {
+        let value = self.next_str(field)?;
+
+        DateTime::parse_from_rfc3339(value.trim())
+            .map(|timestamp| timestamp.with_timezone(&Local))
+            .map_err(|_| self.error(field, LoadErrorKind::InvalidTimestamp(value.to_string())))
+    }
+
+    fn next_customer(&mut self) -> Result<Customer, LoadError> {
+        let first_name = self.next_str(LoadField::FirstName)?;
+        let last_name = self.next_str(LoadField::LastName)?;
+        let value = self.next_str(LoadField::Order)?;
+        let order = customer::parse_order(value).ok_or_else(|| {
+            self.error(
+                LoadField::Order,
+                LoadErrorKind::InvalidOrder(value.to_string()),
+            )
+        })?;
+
+        Ok(Customer::with_order(
+            first_name.to_string(),
+            last_name.to_string(),
+            order,
+        ))
+    }
+
+    fn next_sale(&mut self) -> Result<Sale, LoadError> {
+        let customer = self.next_customer()?;
+        let queue_no = self.next_usize(LoadField::SaleQueue)?;
+        let no_items = self.next_usize(LoadField::SaleItems)?;
+        let price = self.next_usize(LoadField::SalePrice)?;
+        let timestamp = self.next_timestamp(LoadField::SaleTime)?;
+
+        Ok(Sale {
+            customer,
+            queue_no,
+            no_items,
+            price,
+            timestamp,
+            shortfall: Vec::new(),
+        })
+    }
+
+    fn next_product(&mut self) -> Result<Product, LoadError> {
+        let name = self.next_str(LoadField::ProductName)?;
+        let stock = self.next_usize(LoadField::ProductStock)?;
+        let price = self.next_usize(LoadField::ProductPrice)?;
+        let low_threshold = self.next_usize(LoadField::LowThreshold)?;
+        let max_threshold = self.next_usize(LoadField::MaxThreshold)?;
+
+        let product = Product::new(name.to_string(), price, low_threshold, max_threshold).map_err(
+            |error| {
+                self.error(
+                    LoadField::LowThreshold,
+                    LoadErrorKind::InventoryError(error),
+                )
+            },
+        )?;
+
+        if stock > max_threshold {
+            return Err(self.error(
+                LoadField::ProductStock,
+                LoadErrorKind::ExceedsStockMax(max_threshold),
+            ));
+        }
+
+        Ok(product.with_stock(stock))
+    }
+
+    /// Reads the priority and overtake count of a customer loaded earlier in the file.
+    fn next_priority(&mut self, customer: &mut Customer) -> Result<(), LoadError> {
+        let value = self.next_str(LoadField::Priority)?;
+        let priority = PriorityLevel::from_name(value).ok_or_else(|| {
+            self.error(
+                LoadField::Priority,
+                LoadErrorKind::InvalidPriority(value.to_string()),
+            )
+        })?;
+        let overtaken = self.next_usize(LoadField::Overtaken)?;
+
+        customer.set_priority(priority);
+        customer.set_overtaken(overtaken);
+
+        Ok(())
+    }
+
+    fn next_customer_id(&mut self, customer: &mut Customer) -> Result<(), LoadError> {
+        let id = self.next_str(LoadField::CustomerId)?;
+        let id = id.trim().parse().map_err(|_| {
+            self.error(
+                LoadField::CustomerId,
+                LoadErrorKind::InvalidNumber(id.to_string()),
+            )
+        })?;
+
+        *customer = customer.clone().with_id(CustomerId(id)).ok_or_else(|| {
+            self.error(
+                LoadField::CustomerId,
+                LoadErrorKind::CustomerIdTooLarge(CustomerId(id)),
+            )
+        })?;
+
+        Ok(())
+    }
+
+    fn next_stock_change(&mut self) -> Result<StockChange, LoadError> {
+        let product = self.next_str(LoadField::ChangeProduct)?;
+
+        let value = self.next_str(LoadField::ChangeReason)?;
+        let reason = StockReason::from_name(value).ok_or_else(|| {
+            self.error(
+                LoadField::ChangeReason,
+                LoadErrorKind::InvalidReason(value.to_string()),
+            )
+        })?;
+
+        let quantity = self.next_isize(LoadField::ChangeQuantity)?;
+        let stock = self.next_usize(LoadField::ChangeStock)?;
+        let timestamp = self.next_timestamp(LoadField::ChangeTime)?;
+        let operator = self.next_str(LoadField::ChangeOperator)?;
+
+        Ok(StockChange {
+            product: product.to_string(),
+            reason,
+            quantity,
+            stock,
+            timestamp,
+            operator: operator.to_string(),
+        })
+    }
 }
 
 impl Display for Shop {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_fmt(format_args!("{}\n{}\n", self.stock(), self.len()))?;
+        let default_stock = self
+            .inventory
+            .get(DEFAULT_PRODUCT)
+            .map_or(0, |product| product.stock());
+        f.write_fmt(format_args!("{}\n{}\n", default_stock, self.len()))?;
 
         for queue in self.view_data() {
             f.write_fmt(format_args!("{}", queue))?;
         }
 
+        f.write_fmt(format_args!("{}\n", self.waiting_list.len()))?;
+
+        for customer in self.waiting_list.iter() {
+            f.write_fmt(format_args!("{}\n", customer))?;
+        }
+
+        f.write_fmt(format_args!("{}\n", self.ledger.sales().len()))?;
+
+        for sale in self.ledger.sales() {
+            f.write_fmt(format_args!("{}\n", sale))?;
+        }
+
+        // The full catalog comes last, the first line only holds the default product's stock
+        f.write_fmt(format_args!("{}\n", self.inventory.products().len()))?;
+
+        for product in self.inventory.products() {
+            f.write_fmt(format_args!("{}\n", product))?;
+        }
+
+        // Sales that could not cover the whole order, by their position in the ledger
+        let shortfalls = self
+            .ledger
+            .sales()
+            .iter()
+            .enumerate()
+            .filter(|(_, sale)| !sale.shortfall.is_empty())
+            .collect::<Vec<_>>();
+
+        f.write_fmt(format_args!("{}\n", shortfalls.len()))?;
+
+        for (sale_no, sale) in shortfalls {
+            f.write_fmt(format_args!(
+                "{}\n{}\n",
+                sale_no,
+                customer::format_order(&sale.shortfall)
+            ))?;
+        }
+
+        f.write_fmt(format_args!("{}\n", self.stock_log.changes().len()))?;
+
+        for change in self.stock_log.changes() {
+            f.write_fmt(format_args!("{}\n", change))?;
+        }
+
+        // The priority of every queued customer followed by the waiting list, in the order above
+        let customers = self
+            .queues
+            .iter()
+            .flat_map(|queue| queue.view_data())
+            .chain(self.waiting_list.iter())
+            .collect::<Vec<_>>();
+
+        f.write_fmt(format_args!("{}\n", customers.len()))?;
+
+        for customer in customers {
+            f.write_fmt(format_args!(
+                "{}\n{}\n",
+                customer.priority(),
+                customer.overtaken()
+            ))?;
+        }
+
+        // Then the ID of every customer in the same order, followed by the customers in the ledger
+        let customers = self
+            .queues
+            .iter()
+            .flat_map(|queue| queue.view_data())
+            .chain(self.waiting_list.iter())
+            .chain(self.ledger.sales().iter().map(|sale| &sale.customer))
+            .collect::<Vec<_>>();
+
+        f.write_fmt(format_args!("{}\n", customers.len()))?;
+
+        for customer in customers {
+            f.write_fmt(format_args!("{}\n", customer.id()))?;
+        }
+
+        f.write_fmt(format_args!("{}\n", self.partial_serve_policy.key()))?;
+
         Ok(())
     }
 }
 
 impl Shop {
     pub fn new(queue_layout: &[usize]) -> Self {
+        Self::with_strategy(queue_layout, Box::new(ShortestQueue))
+    }
+
+    pub fn with_strategy(
+        queue_layout: &[usize],
+        strategy: Box<dyn QueueAssignmentStrategy>,
+    ) -> Self {
         let mut queues = Vec::with_capacity(queue_layout.len());
 
         for i in 0..queue_layout.len() {
             queues.push(FoodQueue::new(i, queue_layout[i]));
         }
 
-        Self { queues, stock: 0 }
+        Self {
+            queues,
+            inventory: Inventory::default(),
+            journal: None,
+            history: History::default(),
+            strategy,
+            waiting_list: VecDeque::new(),
+            ledger: Ledger::default(),
+            partial_serve_policy: PartialServePolicy::default(),
+            stock_log: StockLog::default(),
+            operator: DEFAULT_OPERATOR.to_string(),
+            events: EventBus::default(),
+            staged: None,
+        }
     }
 
-    pub fn stock(&self) -> usize {
-        self.stock
+    /// Opens the shop with the default product already in stock, logged as its opening stock.
+    /// Meant to be used right after construction, so it is neither journaled nor recorded in the
+    /// history.
+    pub fn with_stock(mut self, stock: usize) -> Self {
+        if let Ok(product) = self
+            .inventory
+            .set_stock(DEFAULT_PRODUCT, stock)
+            .and_then(|_| self.inventory.get(DEFAULT_PRODUCT))
+        {
+            self.stock_log.record_opening(product, &self.operator);
+        }
+
+        self
     }
 
-    pub fn set_stock(&mut self, stock: usize) {
-        self.stock = stock;
+    /// Starts over with empty queues of the given capacities and only the default product in
+    /// stock, as if the shop had just been opened. Like loading, this is not journaled.
+    pub fn reopen(&mut self, queue_layout: &[usize], stock: usize) {
+        let opened = Shop::new(queue_layout).with_stock(stock);
+
+        self.queues = opened.queues;
+        self.inventory = opened.inventory;
+        self.waiting_list = opened.waiting_list;
+        self.ledger = opened.ledger;
+        self.stock_log = opened.stock_log;
+        self.history.clear();
+
+        self.emit(ShopEvent::QueuesReloaded);
     }
 
-    pub fn len(&self) -> usize {
-        self.queues.len()
+    /// Registers a callback that is run after every change to the shop.
+    pub fn subscribe(
+        &mut self,
+        subscriber: impl Fn(&Shop, &ShopEvent) + 'static,
+    ) -> SubscriptionId {
+        self.events.subscribe(subscriber)
     }
 
-    pub fn view_data(&self) -> &[FoodQueue] {
-        self.queues.as_slice()
+    pub fn unsubscribe(&mut self, id: SubscriptionId) {
+        self.events.unsubscribe(id);
     }
 
-    pub fn add_customer(&mut self, customer: Customer) -> Result<&Customer, ShopError> {
-        match self
-            .queues
-            .iter_mut()
-            .filter(|queue| !queue.is_full())
-            .min_by(|queue1, queue2| queue1.len().cmp(&queue2.len()))
-        {
-            Some(queue) => Ok(queue.add_customer(customer).unwrap()),
-            None => Err(ShopError::Full),
+    fn emit(&mut self, event: ShopEvent) {
+        match &mut self.staged {
+            Some(staged) => staged.events.push(event),
+            None => self.events.emit(self, event),
         }
     }
 
-    pub fn remove_customer(
+    pub fn strategy(&self) -> &dyn QueueAssignmentStrategy {
+        self.strategy.as_ref()
+    }
+
+    pub fn set_strategy(&mut self, strategy: Box<dyn QueueAssignmentStrategy>) {
+        self.strategy = strategy;
+    }
+
+    pub fn partial_serve_policy(&self) -> PartialServePolicy {
+        self.partial_serve_policy
+    }
+
+    pub fn set_partial_serve_policy(
         &mut self,
-        queue_no: usize,
-        customer_pos: usize,
-    ) -> Result<Customer, ShopError> {
-        self.queues
-            .get_mut(queue_no)
-            .ok_or_else(|| ShopError::QueueNotFound)?
-            .remove_customer(customer_pos)
-            .or_else(|error| Err(ShopError::QueueError(error)))
+        policy: PartialServePolicy,
+    ) -> Result<(), ShopError> {
+        self.record(JournalEntry::SetPartialServePolicy { policy })?;
+        self.partial_serve_policy = policy;
+
+        Ok(())
     }
 
-    pub fn serve_customer(&mut self, queue_no: usize) -> Result<Customer, ShopError> {
-        let (new_stock, overflow) = self.stock().overflowing_sub(
-            self.queues
-                .get(queue_no)
-                .ok_or_else(|| ShopError::QueueNotFound)?
-                .get_customer(0)
-                .or_else(|error| Err(ShopError::QueueError(error)))?
-                .no_items(),
-        );
+    pub fn operator(&self) -> &str {
+        self.operator.as_str()
+    }
+
+    pub fn set_operator(&mut self, operator: String) {
+        self.operator = operator;
+    }
+
+    pub fn stock_log(&self) -> &StockLog {
+        &self.stock_log
+    }
+
+    pub fn journal(&self) -> Option<&Journal> {
+        self.journal.as_ref()
+    }
 
-        if overflow {
-            return Err(ShopError::StockInsufficient);
+    /// Every mutation after this call is written to the journal before it is applied.
+    pub fn set_journal(&mut self, journal: Option<Journal>) {
+        self.journal = journal;
+    }
+
+    pub fn inventory(&self) -> &Inventory {
+        &self.inventory
+    }
+
+    /// Adds a product to the catalog. The catalog only grows, so this is journaled but cannot be
+    /// undone.
+    pub fn add_product(&mut self, product: Product) -> Result<(), ShopError> {
+        if self.inventory.get(product.name()).is_ok() {
+            return Err(ShopError::InventoryError(InventoryError::DuplicateProduct(
+                product.name().to_string(),
+            )));
         }
-        self.set_stock(new_stock);
 
-        Ok(self.remove_customer(queue_no, 0).unwrap())
+        self.record(JournalEntry::AddProduct {
+            product: product.clone(),
+        })?;
+        self.inventory
+            .add(product.clone())
+            .map_err(ShopError::InventoryError)?;
+        self.stock_log.record_opening(&product, &self.operator);
+
+        self.emit(ShopEvent::ProductsChanged);
+
+        Ok(())
     }
 
-    pub fn get_sorted_customers(&self) -> Vec<&Customer> {
-        let mut sorted_list = self
-            .queues
+    /// Changes the stock of a product, logging the change with the reason and the operator.
+    pub fn set_stock(
+        &mut self,
+        product: &str,
+        stock: usize,
+        reason: StockReason,
+    ) -> Result<(), ShopError> {
+        let previous_stock = self
+            .inventory
+            .get(product)
+            .map_err(ShopError::InventoryError)?
+            .stock();
+        self.write_stock(product, stock, reason)?;
+
+        self.history.push(HistoryEntry::SetStock {
+            product: product.to_string(),
+            previous_stock,
+            stock,
+            reason,
+        });
+
+        Ok(())
+    }
+
+    /// Returns a warning for every product at or below its low threshold.
+    pub fn stock_warnings(&self) -> Vec<StockWarning> {
+        self.inventory
+            .products()
             .iter()
-            .map(|queue| queue.view_data().iter())
-            .flatten()
-            .collect::<Vec<&Customer>>();
-        sorted_list.sort();
+            .filter(|product| product.is_low())
+            .map(|product| {
+                let queued_items = self
+                    .queues
+                    .iter()
+                    .flat_map(|queue| queue.view_data())
+                    .chain(self.waiting_list.iter())
+                    .flat_map(|customer| customer.order())
+                    .filter(|line_item| line_item.product == product.name())
+                    .map(|line_item| line_item.quantity)
+                    .sum::<usize>();
 
-        sorted_list
+                let reorder = (queued_items + product.low_threshold() + 1)
+                    .saturating_sub(product.stock())
+                    .min(product.max_threshold() - product.stock());
+
+                StockWarning {
+                    product: product.name().to_string(),
+                    stock: product.stock(),
+                    queued_items,
+                    reorder,
+                }
+            })
+            .collect()
     }
 
-    pub fn save_to_file(&self, file: &mut File) -> io::Result<usize> {
-        file.write(self.to_string().as_bytes())
+    pub fn ledger(&self) -> &Ledger {
+        &self.ledger
     }
 
-    pub fn load_from_file(&mut self, file: &mut File) {
-        // TODO remove all unwrap calls
-        let mut file_data = String::new();
-        file.read_to_string(&mut file_data).unwrap();
+    pub fn history(&self) -> &History {
+        &self.history
+    }
+
+    pub fn len(&self) -> usize {
+        self.queues.len()
+    }
 
-        let mut lines = file_data.lines();
+    pub fn view_data(&self) -> &[FoodQueue] {
+        self.queues.as_slice()
+    }
 
-        let new_stock: usize = lines.next().unwrap().parse().unwrap();
-        let no_queues: usize = lines.next().unwrap().parse().unwrap();
+    pub fn waiting_list(&self) -> &VecDeque<Customer> {
+        &self.waiting_list
+    }
 
-        let mut new_queues: Vec<FoodQueue> = Vec::with_capacity(no_queues);
+    /// Adds the customer to the queue picked by the strategy, or to the back of the waiting list
+    /// when every queue is full.
+    pub fn add_customer(&mut self, customer: Customer) -> Result<CustomerPlacement, ShopError> {
+        self.inventory
+            .check_order(customer.order())
+            .map_err(ShopError::InventoryError)?;
 
-        for _ in 0..no_queues {
-            let queue_id = lines.next().unwrap().parse().unwrap();
-            let queue_capacity = lines.next().unwrap().parse().unwrap();
-            let queue_length = lines.next().unwrap().parse().unwrap();
+        match self.strategy.select_queue(&self.queues, &customer) {
+            Some(queue_no) => self
+                .place_customer(queue_no, customer, true)
+                .map(|_| CustomerPlacement::Queue(queue_no)),
+            None => {
+                self.join_waiting_list(customer.clone())?;
+                self.history.push(HistoryEntry::WaitCustomer { customer });
 
-            let mut new_queue = FoodQueue::new(queue_id, queue_capacity);
+                Ok(CustomerPlacement::WaitingList(self.waiting_list.len() - 1))
+            }
+        }
+    }
 
-            for _ in 0..queue_length {
-                let first_name = lines.next().unwrap();
-                let last_name = lines.next().unwrap();
-                let no_items = lines.next().unwrap().parse().unwrap();
+    pub fn add_customer_to(
+        &mut self,
+        queue_no: usize,
+        customer: Customer,
+    ) -> Result<&Customer, ShopError> {
+        self.place_customer(queue_no, customer, false)
+    }
 
-                let customer =
-                    Customer::new(first_name.to_string(), last_name.to_string(), no_items);
+    /// Adds the customer to the given queue, telling the strategy when it was the one to pick it.
+    fn place_customer(
+        &mut self,
+        queue_no: usize,
+        customer: Customer,
+        assigned: bool,
+    ) -> Result<&Customer, ShopError> {
+        self.inventory
+            .check_order(customer.order())
+            .map_err(ShopError::InventoryError)?;
 
-                new_queue.add_customer(customer).unwrap();
-            }
+        let customer_pos = self.queue_customer(queue_no, customer.clone())?;
 
-            new_queues.push(new_queue);
+        if assigned {
+            self.strategy.customer_assigned(queue_no);
         }
 
-        self.queues = new_queues;
-        self.stock = new_stock;
+        self.history.push(HistoryEntry::AddCustomer {
+            queue_no,
+            customer_pos,
+            customer,
+            assigned,
+        });
+
+        self.queues[queue_no]
+            .get_customer(customer_pos)
+            .map_err(ShopError::QueueError)
     }
 
-    pub fn search_for_customer(&self, query: &str) -> Vec<&Customer> {
+    /// Returns the queue and position of the customer, or `None` if they are not in a queue.
+    pub fn find_customer(&self, id: CustomerId) -> Option<(usize, usize)> {
         self.queues
             .iter()
-            .map(|queue| queue.view_data().iter())
-            .flatten()
-            .filter(|customer| customer.full_name().contains(query))
-            .collect()
+            .enumerate()
+            .find_map(|(queue_no, queue)| Some((queue_no, queue.position_of(id)?)))
+    }
+
+    pub fn remove_customer_by_id(&mut self, id: CustomerId) -> Result<Customer, ShopError> {
+        let (queue_no, customer_pos) = self
+            .find_customer(id)
+            .ok_or(ShopError::QueueError(FoodQueueError::CustomerNotFound))?;
+
+        self.remove_customer(queue_no, customer_pos)
+    }
+
+    pub fn remove_customer(
+        &mut self,
+        queue_no: usize,
+        customer_pos: usize,
+    ) -> Result<Customer, ShopError> {
+        self.transaction(|shop| {
+            let customer = shop.take_customer(queue_no, customer_pos)?;
+            let promoted = shop.promote_waiting_customer(queue_no)?;
+
+            shop.history.push(HistoryEntry::RemoveCustomer {
+                queue_no,
+                customer_pos,
+                customer: customer.clone(),
+                promoted,
+            });
+
+            Ok(customer)
+        })
+    }
+
+    /// Moves a customer to another queue, where they join like a new customer would. Overtakes
+    /// counted in their old queue do not carry over. This is recorded as a removal followed by an
+    /// addition, so undoing it takes two steps.
+    pub fn move_customer(
+        &mut self,
+        from_queue_no: usize,
+        customer_pos: usize,
+        to_queue_no: usize,
+    ) -> Result<&Customer, ShopError> {
+        self.get_queue(from_queue_no)?
+            .get_customer(customer_pos)
+            .map_err(ShopError::QueueError)?;
+
+        if from_queue_no == to_queue_no {
+            return self.queues[from_queue_no]
+                .get_customer(customer_pos)
+                .map_err(ShopError::QueueError);
+        }
+
+        if self.get_queue(to_queue_no)?.is_full() {
+            return Err(ShopError::QueueError(FoodQueueError::Full));
+        }
+
+        let customer_pos = self.transaction(|shop| {
+            let mut customer = shop.remove_customer(from_queue_no, customer_pos)?;
+            customer.set_overtaken(0);
+
+            let id = customer.id();
+            shop.add_customer_to(to_queue_no, customer)?;
+
+            shop.queues[to_queue_no]
+                .position_of(id)
+                .ok_or(ShopError::QueueError(FoodQueueError::CustomerNotFound))
+        })?;
+
+        self.queues[to_queue_no]
+            .get_customer(customer_pos)
+            .map_err(ShopError::QueueError)
+    }
+
+    /// Serves the customer at the front of the queue. When the stock cannot cover their whole
+    /// order the partial serve policy decides what happens.
+    pub fn serve_customer(&mut self, queue_no: usize) -> Result<ServeOutcome, ShopError> {
+        let policy = self.partial_serve_policy;
+        let operator = self.operator.clone();
+        let served_at = Local::now();
+
+        self.transaction(|shop| {
+            let outcome = shop.take_served_customer(queue_no, served_at, policy, &operator)?;
+            let promoted = if outcome.kept {
+                None
+            } else {
+                shop.promote_waiting_customer(queue_no)?
+            };
+
+            shop.history.push(HistoryEntry::ServeCustomer {
+                queue_no,
+                customer: outcome.customer.clone(),
+                served: outcome.served.clone(),
+                served_at,
+                policy,
+                kept: outcome.kept,
+                promoted,
+            });
+
+            Ok(outcome)
+        })
+    }
+
+    /// Reverts the most recent operation, returning it so callers can describe what was undone.
+    pub fn undo(&mut self) -> Result<HistoryEntry, ShopError> {
+        let entry = self
+            .history
+            .next_undo()
+            .cloned()
+            .ok_or(ShopError::NothingToUndo)?;
+
+        self.transaction(|shop| {
+            match entry.clone() {
+                HistoryEntry::AddCustomer {
+                    queue_no,
+                    customer_pos,
+                    assigned,
+                    ..
+                } => {
+                    shop.withdraw_customer(queue_no, customer_pos)?;
+
+                    if assigned {
+                        shop.strategy.assignment_undone(queue_no);
+                    }
+
+                    Ok(())
+                }
+                HistoryEntry::RemoveCustomer {
+                    queue_no,
+                    customer_pos,
+                    customer,
+                    promoted,
+                } => {
+                    if let Some(promotion) = promoted {
+                        shop.demote_customer(queue_no, promotion)?;
+                    }
+
+                    shop.insert_customer(queue_no, customer_pos, customer)
+                }
+                HistoryEntry::ServeCustomer {
+                    queue_no,
+                    customer,
+                    served,
+                    kept,
+                    promoted,
+                    ..
+                } => {
+                    if let Some(promotion) = promoted {
+                        shop.demote_customer(queue_no, promotion)?;
+                    }
+
+                    // A kept customer is swapped back for the one holding the whole order
+                    if kept {
+                        shop.take_customer(queue_no, 0)?;
+                    }
+
+                    for line_item in served.iter() {
+                        let stock = shop
+                            .inventory
+                            .get(&line_item.product)
+                            .map_err(ShopError::InventoryError)?
+                            .stock();
+                        shop.write_stock(
+                            &line_item.product,
+                            stock + line_item.quantity,
+                            StockReason::Sale,
+                        )?;
+                    }
+
+                    shop.insert_customer(queue_no, 0, customer)?;
+                    shop.refund_sale().map(|_| ())
+                }
+                HistoryEntry::WaitCustomer { .. } => shop.leave_waiting_list().map(|_| ()),
+                HistoryEntry::SetStock {
+                    product,
+                    previous_stock,
+                    reason,
+                    ..
+                } => shop.write_stock(&product, previous_stock, reason),
+            }?;
+
+            shop.history.mark_undone();
+
+            Ok(())
+        })?;
+
+        Ok(entry)
+    }
+
+    pub fn redo(&mut self) -> Result<HistoryEntry, ShopError> {
+        let entry = self
+            .history
+            .next_redo()
+            .cloned()
+            .ok_or(ShopError::NothingToRedo)?;
+
+        self.transaction(|shop| {
+            match entry.clone() {
+                HistoryEntry::AddCustomer {
+                    queue_no,
+                    customer,
+                    assigned,
+                    ..
+                } => {
+                    shop.queue_customer(queue_no, customer)?;
+
+                    if assigned {
+                        shop.strategy.customer_assigned(queue_no);
+                    }
+
+                    Ok(())
+                }
+                HistoryEntry::RemoveCustomer {
+                    queue_no,
+                    customer_pos,
+                    promoted,
+                    ..
+                } => {
+                    shop.take_customer(queue_no, customer_pos)?;
+
+                    if promoted.is_some() {
+                        shop.promote_waiting_customer(queue_no)?;
+                    }
+
+                    Ok(())
+                }
+                HistoryEntry::ServeCustomer {
+                    queue_no,
+                    served_at,
+                    policy,
+                    promoted,
+                    ..
+                } => {
+                    let operator = shop.operator.clone();
+                    shop.take_served_customer(queue_no, served_at, policy, &operator)?;
+
+                    if promoted.is_some() {
+                        shop.promote_waiting_customer(queue_no)?;
+                    }
+
+                    Ok(())
+                }
+                HistoryEntry::WaitCustomer { customer } => shop.join_waiting_list(customer),
+                HistoryEntry::SetStock {
+                    product,
+                    stock,
+                    reason,
+                    ..
+                } => shop.write_stock(&product, stock, reason),
+            }?;
+
+            shop.history.mark_redone();
+
+            Ok(())
+        })?;
+
+        Ok(entry)
+    }
+
+    /// Applies journal entries on top of the current state. Nothing changes unless every entry
+    /// applies cleanly. Snapshot and opening entries are skipped since loading them or reopening
+    /// the shop is up to the caller.
+    pub fn replay_journal(&mut self, entries: &[JournalEntry]) -> Result<(), ShopError> {
+        let mut replayed = self.clone();
+        replayed.journal = None;
+
+        for entry in entries {
+            match entry.clone() {
+                JournalEntry::Snapshot { .. } | JournalEntry::Opened { .. } => Ok(()),
+                JournalEntry::AddCustomer { queue_no, customer } => {
+                    let customer_pos = replayed.get_queue(queue_no)?.len();
+                    replayed.insert_customer(queue_no, customer_pos, customer)
+                }
+                JournalEntry::InsertCustomer {
+                    queue_no,
+                    customer_pos,
+                    customer,
+                } => replayed.insert_customer(queue_no, customer_pos, customer),
+                JournalEntry::RemoveCustomer {
+                    queue_no,
+                    customer_pos,
+                } => replayed.take_customer(queue_no, customer_pos).map(|_| ()),
+                JournalEntry::QueueCustomer { queue_no, customer } => {
+                    replayed.queue_customer(queue_no, customer).map(|_| ())
+                }
+                JournalEntry::WithdrawCustomer {
+                    queue_no,
+                    customer_pos,
+                } => replayed.withdraw_customer(queue_no, customer_pos),
+                JournalEntry::ServeCustomer {
+                    queue_no,
+                    served_at,
+                    policy,
+                    operator,
+                } => replayed
+                    .take_served_customer(queue_no, served_at, policy, &operator)
+                    .map(|_| ()),
+                JournalEntry::RefundSale => replayed.refund_sale().map(|_| ()),
+                JournalEntry::SetPartialServePolicy { policy } => {
+                    replayed.set_partial_serve_policy(policy)
+                }
+                JournalEntry::ChangeStock { change } => replayed.apply_stock_change(change),
+                JournalEntry::AddProduct { product } => replayed.add_product(product),
+                JournalEntry::JoinWaitingList { customer } => replayed.join_waiting_list(customer),
+                JournalEntry::LeaveWaitingList => replayed.leave_waiting_list().map(|_| ()),
+                JournalEntry::PromoteCustomer { queue_no } => {
+                    replayed.promote_waiting_customer(queue_no).map(|_| ())
+                }
+                JournalEntry::DemoteCustomer {
+                    queue_no,
+                    customer_pos,
+                    waiting_pos,
+                } => replayed.demote_customer(
+                    queue_no,
+                    Promotion {
+                        waiting_pos,
+                        customer_pos,
+                    },
+                ),
+            }?;
+        }
+
+        replayed.journal = self.journal.take();
+        replayed.events = std::mem::take(&mut self.events);
+        *self = replayed;
+
+        self.emit(ShopEvent::QueuesReloaded);
+
+        Ok(())
+    }
+
+    // The functions below are the only ones that change the queues, waiting list or stock. Each one
+    // validates the change, writes it to the journal and then applies it, but leaves the history
+    // alone.
+
+    fn insert_customer(
+        &mut self,
+        queue_no: usize,
+        customer_pos: usize,
+        customer: Customer,
+    ) -> Result<(), ShopError> {
+        let queue = self.get_queue(queue_no)?;
+
+        if queue.is_full() {
+            return Err(ShopError::QueueError(FoodQueueError::Full));
+        }
+
+        if customer_pos > queue.len() {
+            return Err(ShopError::QueueError(FoodQueueError::CustomerNotFound));
+        }
+
+        self.record(if customer_pos == queue.len() {
+            JournalEntry::AddCustomer {
+                queue_no,
+                customer: customer.clone(),
+            }
+        } else {
+            JournalEntry::InsertCustomer {
+                queue_no,
+                customer_pos,
+                customer: customer.clone(),
+            }
+        })?;
+
+        self.queues[queue_no]
+            .insert_customer(customer_pos, customer)
+            .map_err(ShopError::QueueError)?;
+
+        self.emit(ShopEvent::CustomerAdded { queue_no });
+
+        Ok(())
+    }
+
+    fn take_customer(
+        &mut self,
+        queue_no: usize,
+        customer_pos: usize,
+    ) -> Result<Customer, ShopError> {
+        self.get_queue(queue_no)?
+            .get_customer(customer_pos)
+            .map_err(ShopError::QueueError)?;
+
+        self.record(JournalEntry::RemoveCustomer {
+            queue_no,
+            customer_pos,
+        })?;
+
+        let customer = self.queues[queue_no]
+            .remove_customer(customer_pos)
+            .map_err(ShopError::QueueError)?;
+
+        self.emit(ShopEvent::CustomerRemoved { queue_no });
+
+        Ok(customer)
+    }
+
+    /// Adds the customer at their priority position in the queue, returning where they joined.
+    fn queue_customer(&mut self, queue_no: usize, customer: Customer) -> Result<usize, ShopError> {
+        if self.get_queue(queue_no)?.is_full() {
+            return Err(ShopError::QueueError(FoodQueueError::Full));
+        }
+
+        self.record(JournalEntry::QueueCustomer {
+            queue_no,
+            customer: customer.clone(),
+        })?;
+
+        let customer_pos = self.queues[queue_no]
+            .queue_customer(customer)
+            .map_err(ShopError::QueueError)?;
+
+        self.emit(ShopEvent::CustomerAdded { queue_no });
+
+        Ok(customer_pos)
+    }
+
+    /// Reverses `queue_customer`, so the queue must be as `queue_customer` left it.
+    fn withdraw_customer(&mut self, queue_no: usize, customer_pos: usize) -> Result<(), ShopError> {
+        self.get_queue(queue_no)?
+            .get_customer(customer_pos)
+            .map_err(ShopError::QueueError)?;
+
+        self.record(JournalEntry::WithdrawCustomer {
+            queue_no,
+            customer_pos,
+        })?;
+
+        self.queues[queue_no]
+            .withdraw_customer(customer_pos)
+            .map_err(ShopError::QueueError)?;
+
+        self.emit(ShopEvent::CustomerRemoved { queue_no });
+
+        Ok(())
+    }
+
+    fn take_served_customer(
+        &mut self,
+        queue_no: usize,
+        served_at: DateTime<Local>,
+        policy: PartialServePolicy,
+        operator: &str,
+    ) -> Result<ServeOutcome, ShopError> {
+        let order = self
+            .get_queue(queue_no)?
+            .get_customer(0)
+            .map_err(ShopError::QueueError)?
+            .order();
+
+        // Every product is checked before anything is taken out, so a refused order leaves the
+        // stock untouched
+        let (served, shortfall) = match self.inventory.remaining_after(order) {
+            Ok(_) => (order.to_vec(), Vec::new()),
+            Err(error @ InventoryError::ProductShort { .. })
+                if policy != PartialServePolicy::Refuse =>
+            {
+                let (served, shortfall) = self
+                    .inventory
+                    .split_order(order)
+                    .map_err(ShopError::InventoryError)?;
+
+                if served.is_empty() {
+                    return Err(ShopError::InventoryError(error));
+                }
+
+                (served, shortfall)
+            }
+            Err(error) => return Err(ShopError::InventoryError(error)),
+        };
+
+        let remaining = self
+            .inventory
+            .remaining_after(&served)
+            .map_err(ShopError::InventoryError)?;
+        let price = self
+            .inventory
+            .price_of(&served)
+            .map_err(ShopError::InventoryError)?;
+
+        self.record(JournalEntry::ServeCustomer {
+            queue_no,
+            served_at,
+            policy,
+            operator: operator.to_string(),
+        })?;
+
+        for (product, stock) in remaining.iter() {
+            let previous_stock = self
+                .inventory
+                .get(product)
+                .map_err(ShopError::InventoryError)?
+                .stock();
+            self.inventory
+                .set_stock(product, *stock)
+                .map_err(ShopError::InventoryError)?;
+
+            self.stock_log.record(StockChange {
+                product: product.clone(),
+                reason: StockReason::Sale,
+                quantity: *stock as isize - previous_stock as isize,
+                stock: *stock,
+                timestamp: served_at,
+                operator: operator.to_string(),
+            });
+        }
+
+        let kept = policy == PartialServePolicy::KeepRemaining && !shortfall.is_empty();
+        let customer = if kept {
+            let mut remaining_customer = self.queues[queue_no]
+                .get_customer(0)
+                .map_err(ShopError::QueueError)?
+                .clone();
+            remaining_customer.set_order(shortfall.clone());
+
+            self.queues[queue_no].replace_customer(0, remaining_customer)
+        } else {
+            self.queues[queue_no].remove_customer(0)
+        }
+        .map_err(ShopError::QueueError)?;
+
+        let no_items = served.iter().map(|line_item| line_item.quantity).sum();
+        self.ledger.record(Sale {
+            customer: customer.clone(),
+            queue_no,
+            no_items,
+            price,
+            timestamp: served_at,
+            shortfall: shortfall.clone(),
+        });
+
+        self.emit(ShopEvent::CustomerServed { queue_no });
+
+        for (product, stock) in remaining {
+            self.emit(ShopEvent::StockChanged { product, stock });
+        }
+
+        for warning in self.stock_warnings() {
+            if customer
+                .order()
+                .iter()
+                .any(|line_item| line_item.product == warning.product)
+            {
+                self.emit(ShopEvent::StockLow(warning));
+            }
+        }
+
+        Ok(ServeOutcome {
+            customer,
+            served,
+            shortfall,
+            kept,
+        })
+    }
+
+    fn refund_sale(&mut self) -> Result<Sale, ShopError> {
+        if self.ledger.sales().is_empty() {
+            return Err(ShopError::NothingToRefund);
+        }
+
+        self.record(JournalEntry::RefundSale)?;
+
+        let sale = self
+            .ledger
+            .refund_last()
+            .ok_or(ShopError::NothingToRefund)?;
+
+        if sale.queue_no < self.queues.len() {
+            self.emit(ShopEvent::SaleRefunded {
+                queue_no: sale.queue_no,
+            });
+        }
+
+        Ok(sale)
+    }
+
+    fn write_stock(
+        &mut self,
+        product: &str,
+        stock: usize,
+        reason: StockReason,
+    ) -> Result<(), ShopError> {
+        let previous_stock = self
+            .inventory
+            .get(product)
+            .map_err(ShopError::InventoryError)?
+            .stock();
+
+        self.apply_stock_change(StockChange {
+            product: product.to_string(),
+            reason,
+            quantity: stock as isize - previous_stock as isize,
+            stock,
+            timestamp: Local::now(),
+            operator: self.operator.clone(),
+        })
+    }
+
+    fn apply_stock_change(&mut self, change: StockChange) -> Result<(), ShopError> {
+        self.inventory
+            .get(&change.product)
+            .map_err(ShopError::InventoryError)?;
+
+        self.record(JournalEntry::ChangeStock {
+            change: change.clone(),
+        })?;
+        self.inventory
+            .set_stock(&change.product, change.stock)
+            .map_err(ShopError::InventoryError)?;
+
+        let product = change.product.clone();
+        let stock = change.stock;
+        self.stock_log.record(change);
+
+        self.emit(ShopEvent::StockChanged { product, stock });
+
+        Ok(())
+    }
+
+    fn join_waiting_list(&mut self, customer: Customer) -> Result<(), ShopError> {
+        self.record(JournalEntry::JoinWaitingList {
+            customer: customer.clone(),
+        })?;
+        self.waiting_list.push_back(customer);
+
+        self.emit(ShopEvent::WaitingListChanged);
+
+        Ok(())
+    }
+
+    fn leave_waiting_list(&mut self) -> Result<Customer, ShopError> {
+        if self.waiting_list.is_empty() {
+            return Err(ShopError::QueueError(FoodQueueError::Empty));
+        }
+
+        self.record(JournalEntry::LeaveWaitingList)?;
+
+        let customer = self
+            .waiting_list
+            .pop_back()
+            .ok_or(ShopError::QueueError(FoodQueueError::Empty))?;
+
+        self.emit(ShopEvent::WaitingListChanged);
+
+        Ok(customer)
+    }
+
+    /// Moves the waiting customer with the highest priority into the queue, the one who has
+    /// waited longest among equals. They join at their priority position like any other customer.
+    fn promote_waiting_customer(
+        &mut self,
+        queue_no: usize,
+    ) -> Result<Option<Promotion>, ShopError> {
+        if self.get_queue(queue_no)?.is_full() {
+            return Ok(None);
+        }
+
+        // `max_by_key` settles ties on the last element, hence searching from the back
+        let waiting_pos = match self
+            .waiting_list
+            .iter()
+            .enumerate()
+            .rev()
+            .max_by_key(|(_, customer)| customer.priority())
+        {
+            Some((waiting_pos, _)) => waiting_pos,
+            None => return Ok(None),
+        };
+
+        self.record(JournalEntry::PromoteCustomer { queue_no })?;
+
+        let customer = self
+            .waiting_list
+            .remove(waiting_pos)
+            .ok_or(ShopError::QueueError(FoodQueueError::CustomerNotFound))?;
+        let customer_pos = self.queues[queue_no]
+            .queue_customer(customer)
+            .map_err(ShopError::QueueError)?;
+
+        self.emit(ShopEvent::CustomerAdded { queue_no });
+        self.emit(ShopEvent::WaitingListChanged);
+
+        Ok(Some(Promotion {
+            waiting_pos,
+            customer_pos,
+        }))
+    }
+
+    /// Reverses `promote_waiting_customer`, so the queue must be as it was left by the promotion.
+    fn demote_customer(&mut self, queue_no: usize, promotion: Promotion) -> Result<(), ShopError> {
+        self.get_queue(queue_no)?
+            .get_customer(promotion.customer_pos)
+            .map_err(ShopError::QueueError)?;
+
+        if promotion.waiting_pos > self.waiting_list.len() {
+            return Err(ShopError::QueueError(FoodQueueError::CustomerNotFound));
+        }
+
+        self.record(JournalEntry::DemoteCustomer {
+            queue_no,
+            customer_pos: promotion.customer_pos,
+            waiting_pos: promotion.waiting_pos,
+        })?;
+
+        let customer = self.queues[queue_no]
+            .withdraw_customer(promotion.customer_pos)
+            .map_err(ShopError::QueueError)?;
+        self.waiting_list.insert(promotion.waiting_pos, customer);
+
+        self.emit(ShopEvent::CustomerRemoved { queue_no });
+        self.emit(ShopEvent::WaitingListChanged);
+
+        Ok(())
+    }
+
+    fn get_queue(&self, queue_no: usize) -> Result<&FoodQueue, ShopError> {
+        self.queues.get(queue_no).ok_or(ShopError::QueueNotFound)
+    }
+
+    fn record(&mut self, entry: JournalEntry) -> Result<(), ShopError> {
+        if let Some(staged) = &mut self.staged {
+            staged.entries.push(entry);
+            return Ok(());
+        }
+
+        match &self.journal {
+            Some(journal) => journal.append(&entry).map_err(ShopError::JournalError),
+            None => Ok(()),
+        }
+    }
+
+    /// Runs an operation made of several steps on a copy of the shop and only swaps it in once
+    /// every step has succeeded, so a failure part way through leaves nothing half done. The
+    /// journal entries are written together and the events are emitted after the swap.
+    fn transaction<T>(
+        &mut self,
+        operation: impl FnOnce(&mut Shop) -> Result<T, ShopError>,
+    ) -> Result<T, ShopError> {
+        // An enclosing transaction already throws everything away when a step fails
+        if self.staged.is_some() {
+            return operation(self);
+        }
+
+        let mut staged = self.clone();
+        staged.journal = None;
+        staged.staged = Some(Staged::default());
+
+        let result = operation(&mut staged)?;
+        let Staged { entries, events } = staged.staged.take().unwrap_or_default();
+
+        if let Some(journal) = &self.journal {
+            journal
+                .append_all(&entries)
+                .map_err(ShopError::JournalError)?;
+        }
+
+        staged.journal = self.journal.take();
+        staged.events = std::mem::take(&mut self.events);
+        *self = staged;
+
+        for event in events {
+            self.emit(event);
+        }
+
+        Ok(result)
+    }
+
+    pub fn get_sorted_customers(&self) -> Vec<&Customer> {
+        let mut sorted_list = self
+            .queues
+            .iter()
+            .map(|queue| queue.view_data().iter())
+            .flatten()
+            .collect::<Vec<&Customer>>();
+        sorted_list.sort();
+
+        sorted_list
+    }
+
+    pub fn save_to_file(&self, file: &mut File) -> io::Result<()> {
+        file.write_all(self.to_string().as_bytes())
+    }
+
+    pub fn save_to_json(&self, file: &mut File) -> io::Result<()> {
+        let save_data = SaveData {
+            version: SAVE_FORMAT_VERSION,
+            inventory: self.inventory.clone(),
+            queues: self.queues.clone(),
+            waiting_list: self.waiting_list.clone(),
+            ledger: self.ledger.clone(),
+            stock_log: self.stock_log.clone(),
+            partial_serve_policy: self.partial_serve_policy,
+        };
+
+        serde_json::to_writer_pretty(file, &save_data).map_err(io::Error::from)
+    }
+
+    pub fn load_from_json(&mut self, file: &mut File) -> Result<(), LoadError> {
+        let save_data: SaveData = serde_json::from_reader(file).map_err(LoadError::JsonError)?;
+
+        if save_data.version > SAVE_FORMAT_VERSION {
+            return Err(LoadError::UnsupportedVersion(save_data.version));
+        }
+
+        let inventory =
+            Inventory::from_products(save_data.inventory.products().to_vec()).map_err(|error| {
+                LoadError::InvalidValue {
+                    field: LoadField::ProductName,
+                    kind: LoadErrorKind::InventoryError(error),
+                }
+            })?;
+
+        Self::validate(&inventory, &save_data.queues, &save_data.waiting_list)?;
+
+        self.queues = save_data.queues;
+        self.inventory = inventory;
+        self.waiting_list = save_data.waiting_list;
+        self.ledger = save_data.ledger;
+        self.stock_log = save_data.stock_log;
+        self.stock_log
+            .record_missing_openings(&self.inventory, &self.operator);
+        self.partial_serve_policy = save_data.partial_serve_policy;
+        self.history.clear();
+
+        self.emit(ShopEvent::QueuesReloaded);
+
+        Ok(())
+    }
+
+    pub fn save(&self, file: &mut File, format: SaveFormat) -> io::Result<()> {
+        match format {
+            SaveFormat::Text => self.save_to_file(file),
+            SaveFormat::Json => self.save_to_json(file),
+        }
+    }
+
+    pub fn load(&mut self, file: &mut File, format: SaveFormat) -> Result<(), LoadError> {
+        match format {
+            SaveFormat::Text => self.load_from_file(file),
+            SaveFormat::Json => self.load_from_json(file),
+        }
+    }
+
+    fn validate(
+        inventory: &Inventory,
+        queues: &[FoodQueue],
+        waiting_list: &VecDeque<Customer>,
+    ) -> Result<(), LoadError> {
+        for product in inventory.products() {
+            if product.low_threshold() >= product.max_threshold() {
+                return Err(LoadError::InvalidValue {
+                    field: LoadField::LowThreshold,
+                    kind: LoadErrorKind::InventoryError(InventoryError::InvalidThresholds),
+                });
+            }
+
+            if product.stock() > product.max_threshold() {
+                return Err(LoadError::InvalidValue {
+                    field: LoadField::ProductStock,
+                    kind: LoadErrorKind::ExceedsStockMax(product.max_threshold()),
+                });
+            }
+        }
+
+        let customers = queues
+            .iter()
+            .flat_map(|queue| queue.view_data())
+            .chain(waiting_list.iter());
+
+        let mut ids = HashSet::new();
+
+        for customer in customers {
+            inventory
+                .check_order(customer.order())
+                .map_err(|error| LoadError::InvalidValue {
+                    field: LoadField::Order,
+                    kind: LoadErrorKind::InventoryError(error),
+                })?;
+
+            if !ids.insert(customer.id()) {
+                return Err(LoadError::InvalidValue {
+                    field: LoadField::CustomerId,
+                    kind: LoadErrorKind::DuplicateCustomerId(customer.id()),
+                });
+            }
+        }
+
+        for (i, queue) in queues.iter().enumerate() {
+            if queues[..i].iter().any(|other| other.id() == queue.id()) {
+                return Err(LoadError::InvalidValue {
+                    field: LoadField::QueueId,
+                    kind: LoadErrorKind::DuplicateQueueId,
+                });
+            }
+
+            if queue.len() > queue.capacity() {
+                return Err(LoadError::InvalidValue {
+                    field: LoadField::Length,
+                    kind: LoadErrorKind::ExceedsCapacity(queue.capacity()),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn load_from_file(&mut self, file: &mut File) -> Result<(), LoadError> {
+        let mut file_data = String::new();
+        file.read_to_string(&mut file_data)
+            .map_err(LoadError::IOError)?;
+
+        let mut lines = LineReader::new(&file_data);
+
+        let new_stock = lines.next_usize(LoadField::Stock)?;
+        if new_stock > STOCK_MAX_THRESHOLD {
+            return Err(lines.error(
+                LoadField::Stock,
+                LoadErrorKind::ExceedsStockMax(STOCK_MAX_THRESHOLD),
+            ));
+        }
+
+        let no_queues = lines.next_usize(LoadField::QueueCount)?;
+
+        let mut new_queues: Vec<FoodQueue> = Vec::new();
+
+        for _ in 0..no_queues {
+            let queue_id = lines.next_usize(LoadField::QueueId)?;
+            if new_queues.iter().any(|queue| queue.id() == queue_id) {
+                return Err(lines.error(LoadField::QueueId, LoadErrorKind::DuplicateQueueId));
+            }
+
+            let queue_capacity = lines.next_usize(LoadField::Capacity)?;
+            let queue_length = lines.next_usize(LoadField::Length)?;
+            if queue_length > queue_capacity {
+                return Err(lines.error(
+                    LoadField::Length,
+                    LoadErrorKind::ExceedsCapacity(queue_capacity),
+                ));
+            }
+
+            let mut new_queue = FoodQueue::new(queue_id, queue_capacity);
+
+            for _ in 0..queue_length {
+                let customer = lines.next_customer()?;
+
+                new_queue.add_customer(customer).map_err(|_| {
+                    lines.error(
+                        LoadField::Length,
+                        LoadErrorKind::ExceedsCapacity(queue_capacity),
+                    )
+                })?;
+            }
+
+            new_queues.push(new_queue);
+        }
+
+        // Files written before the waiting list existed simply end after the queues
+        let mut new_waiting_list = VecDeque::new();
+
+        if lines.has_next() {
+            let waiting_length = lines.next_usize(LoadField::WaitingCount)?;
+
+            for _ in 0..waiting_length {
+                new_waiting_list.push_back(lines.next_customer()?);
+            }
+        }
+
+        // Likewise the ledger is only present in files written after sales were recorded
+        let mut new_sales = Vec::new();
+
+        if lines.has_next() {
+            let no_sales = lines.next_usize(LoadField::SaleCount)?;
+
+            for _ in 0..no_sales {
+                new_sales.push(lines.next_sale()?);
+            }
+        }
+
+        // Then the catalog, which replaces the stock read from the first line
+        let mut new_inventory = Inventory::default();
+        let _ = new_inventory.set_stock(DEFAULT_PRODUCT, new_stock);
+
+        if lines.has_next() {
+            let no_products = lines.next_usize(LoadField::ProductCount)?;
+            let mut products = Vec::new();
+
+            for _ in 0..no_products {
+                products.push(lines.next_product()?);
+            }
+
+            new_inventory = Inventory::from_products(products).map_err(|error| {
+                lines.error(LoadField::ProductName, LoadErrorKind::InventoryError(error))
+            })?;
+        }
+
+        if lines.has_next() {
+            let no_shortfalls = lines.next_usize(LoadField::ShortfallCount)?;
+
+            for _ in 0..no_shortfalls {
+                let sale_no = lines.next_usize(LoadField::ShortfallSale)?;
+                let value = lines.next_str(LoadField::Shortfall)?;
+                let shortfall = customer::parse_order(value).ok_or_else(|| {
+                    lines.error(
+                        LoadField::Shortfall,
+                        LoadErrorKind::InvalidOrder(value.to_string()),
+                    )
+                })?;
+
+                new_sales
+                    .get_mut(sale_no)
+                    .ok_or_else(|| {
+                        lines.error(
+                            LoadField::ShortfallSale,
+                            LoadErrorKind::SaleNotFound(sale_no),
+                        )
+                    })?
+                    .shortfall = shortfall;
+            }
+        }
+
+        let mut new_stock_log = StockLog::default();
+
+        if lines.has_next() {
+            let no_changes = lines.next_usize(LoadField::ChangeCount)?;
+
+            for _ in 0..no_changes {
+                new_stock_log.record(lines.next_stock_change()?);
+            }
+        }
+
+        if lines.has_next() {
+            let customers = new_queues
+                .iter_mut()
+                .flat_map(|queue| queue.customers_mut())
+                .chain(new_waiting_list.iter_mut())
+                .collect::<Vec<_>>();
+
+            let no_customers = lines.next_usize(LoadField::PriorityCount)?;
+            if no_customers != customers.len() {
+                return Err(lines.error(
+                    LoadField::PriorityCount,
+                    LoadErrorKind::CustomerCountMismatch(customers.len()),
+                ));
+            }
+
+            for customer in customers {
+                lines.next_priority(customer)?;
+            }
+        }
+
+        if lines.has_next() {
+            let customers = new_queues
+                .iter_mut()
+                .flat_map(|queue| queue.customers_mut())
+                .chain(new_waiting_list.iter_mut())
+                .chain(new_sales.iter_mut().map(|sale| &mut sale.customer))
+                .collect::<Vec<_>>();
+
+            let no_customers = lines.next_usize(LoadField::CustomerIdCount)?;
+            if no_customers != customers.len() {
+                return Err(lines.error(
+                    LoadField::CustomerIdCount,
+                    LoadErrorKind::CustomerCountMismatch(customers.len()),
+                ));
+            }
+
+            for customer in customers {
+                lines.next_customer_id(customer)?;
+            }
+        }
+
+        // Older files keep whichever policy the shop already had
+        let mut new_policy = self.partial_serve_policy;
+
+        if lines.has_next() {
+            let value = lines.next_str(LoadField::PartialServePolicy)?;
+            new_policy = PartialServePolicy::from_key(value).ok_or_else(|| {
+                lines.error(
+                    LoadField::PartialServePolicy,
+                    LoadErrorKind::InvalidPolicy(value.to_string()),
+                )
+            })?;
+        }
+
+        let mut new_ledger = Ledger::default();
+        for sale in new_sales {
+            new_ledger.record(sale);
+        }
+
+        Self::validate(&new_inventory, &new_queues, &new_waiting_list)?;
+
+        self.queues = new_queues;
+        self.inventory = new_inventory;
+        self.waiting_list = new_waiting_list;
+        self.ledger = new_ledger;
+        self.stock_log = new_stock_log;
+        self.stock_log
+            .record_missing_openings(&self.inventory, &self.operator);
+        self.partial_serve_policy = new_policy;
+        self.history.clear();
+
+        self.emit(ShopEvent::QueuesReloaded);
+
+        Ok(())
+    }
+
+    /// Searches the queued customers with the default options, see `search`.
+    pub fn search_for_customer(&self, query: &str) -> Vec<SearchResult<'_>> {
+        self.search(&SearchOptions::new(query))
+    }
+
+    /// Returns every queued customer matching the options, best matches first. Ties keep queue
+    /// order.
+    pub fn search(&self, options: &SearchOptions) -> Vec<SearchResult<'_>> {
+        let mut results = Vec::new();
+
+        for (queue_no, queue) in self.queues.iter().enumerate() {
+            for (customer_pos, customer) in queue.view_data().iter().enumerate() {
+                if let Some(score) = options.score(customer) {
+                    results.push(SearchResult {
+                        queue_no,
+                        customer_pos,
+                        customer,
+                        score,
+                    });
+                }
+            }
+        }
+
+        results.sort_by_key(|result| (result.score, result.queue_no, result.customer_pos));
+
+        results
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env, fs,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    use super::*;
+    use crate::{food_queue::MAX_OVERTAKES, queue_strategy::RoundRobin};
+
+    /// Loading reads from a `File`, so the data goes through a temporary one.
+    fn load_text(shop: &mut Shop, data: &str) -> Result<(), LoadError> {
+        static FILE_NO: AtomicUsize = AtomicUsize::new(0);
+
+        let path = env::temp_dir().join(format!(
+            "sdii_cw_load_test_{}_{}.txt",
+            std::process::id(),
+            FILE_NO.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::write(&path, data).unwrap();
+
+        let result = shop.load(&mut File::open(&path).unwrap(), SaveFormat::Text);
+        fs::remove_file(&path).unwrap();
+
+        result
+    }
+
+    fn assert_invalid_field(
+        result: Result<(), LoadError>,
+        expected_line: usize,
+        expected_field: LoadField,
+    ) -> LoadErrorKind {
+        match result {
+            Err(LoadError::InvalidField { line, field, kind }) => {
+                assert_eq!(line, expected_line);
+                assert_eq!(field, expected_field);
+                kind
+            }
+            result => panic!("expected an invalid {expected_field} field, got {result:?}"),
+        }
+    }
+
+    #[test]
+    fn loads_a_legacy_file() {
+        let mut shop = Shop::new(&[1]);
+
+        load_text(&mut shop, "5\n2\n0\n2\n1\nJane\nDoe\n3\n1\n3\n0\n").unwrap();
+
+        assert_eq!(shop.len(), 2);
+        assert_eq!(shop.view_data()[0].len(), 1);
+        assert_eq!(shop.view_data()[0].view_data()[0].full_name(), "Jane Doe");
+        assert_eq!(shop.view_data()[0].view_data()[0].no_items(), 3);
+        assert_eq!(shop.inventory().get(DEFAULT_PRODUCT).unwrap().stock(), 5);
+    }
+
+    #[test]
+    fn rejects_a_truncated_file() {
+        let result = load_text(&mut Shop::new(&[1]), "5\n1\n0\n2\n");
+
+        let kind = assert_invalid_field(result, 5, LoadField::Length);
+        assert!(matches!(kind, LoadErrorKind::Missing));
+    }
+
+    #[test]
+    fn rejects_a_truncated_customer() {
+        let result = load_text(&mut Shop::new(&[1]), "5\n1\n0\n2\n1\nJane\n");
+
+        let kind = assert_invalid_field(result, 7, LoadField::LastName);
+        assert!(matches!(kind, LoadErrorKind::Missing));
+    }
+
+    #[test]
+    fn rejects_a_non_numeric_field() {
+        let result = load_text(&mut Shop::new(&[1]), "5\none\n");
+
+        let kind = assert_invalid_field(result, 2, LoadField::QueueCount);
+        assert!(matches!(kind, LoadErrorKind::InvalidNumber(value) if value == "one"));
+    }
+
+    #[test]
+    fn rejects_an_invalid_order() {
+        let result = load_text(&mut Shop::new(&[1]), "5\n1\n0\n2\n1\nJane\nDoe\nlots\n");
+
+        let kind = assert_invalid_field(result, 8, LoadField::Order);
+        assert!(matches!(kind, LoadErrorKind::InvalidOrder(value) if value == "lots"));
+    }
+
+    #[test]
+    fn rejects_a_length_above_capacity() {
+        let result = load_text(&mut Shop::new(&[1]), "5\n1\n0\n1\n2\n");
+
+        let kind = assert_invalid_field(result, 5, LoadField::Length);
+        assert!(matches!(kind, LoadErrorKind::ExceedsCapacity(1)));
+    }
+
+    #[test]
+    fn rejects_duplicate_queue_ids() {
+        let result = load_text(&mut Shop::new(&[1]), "5\n2\n0\n2\n0\n0\n3\n0\n");
+
+        let kind = assert_invalid_field(result, 6, LoadField::QueueId);
+        assert!(matches!(kind, LoadErrorKind::DuplicateQueueId));
+    }
+
+    #[test]
+    fn rejects_stock_above_the_maximum() {
+        let data = format!("{}\n1\n0\n2\n0\n", STOCK_MAX_THRESHOLD + 1);
+        let result = load_text(&mut Shop::new(&[1]), &data);
+
+        let kind = assert_invalid_field(result, 1, LoadField::Stock);
+        assert!(matches!(
+            kind,
+            LoadErrorKind::ExceedsStockMax(STOCK_MAX_THRESHOLD)
+        ));
+    }
+
+    #[test]
+    fn rejects_a_product_count_past_the_end_of_the_file() {
+        let data = format!("5\n1\n0\n2\n0\n0\n0\n{}\n", usize::MAX);
+        let result = load_text(&mut Shop::new(&[1]), &data);
+
+        assert!(matches!(
+            result,
+            Err(LoadError::InvalidField {
+                line: 9,
+                kind: LoadErrorKind::Missing,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn saves_the_partial_serve_policy() {
+        let mut shop = Shop::new(&[2]);
+        shop.set_partial_serve_policy(PartialServePolicy::KeepRemaining)
+            .unwrap();
+
+        let mut loaded = Shop::new(&[1]);
+        load_text(&mut loaded, &shop.to_string()).unwrap();
+        assert_eq!(
+            loaded.partial_serve_policy(),
+            PartialServePolicy::KeepRemaining
+        );
+
+        // A legacy file has no policy and leaves it alone
+        load_text(&mut loaded, "5\n1\n0\n2\n0\n").unwrap();
+        assert_eq!(
+            loaded.partial_serve_policy(),
+            PartialServePolicy::KeepRemaining
+        );
+    }
+
+    #[test]
+    fn reconciles_against_the_opening_stock() {
+        let mut shop = Shop::new(&[2]).with_stock(20);
+        shop.add_customer(Customer::new("Jane".to_string(), "Doe".to_string(), 3))
+            .unwrap();
+        shop.serve_customer(0).unwrap();
+        shop.set_stock(DEFAULT_PRODUCT, 15, StockReason::Correction)
+            .unwrap();
+
+        let report = shop.stock_log().reconcile(shop.inventory());
+        let reconciliation = &report[DEFAULT_PRODUCT];
+
+        assert_eq!(reconciliation.opening, 20);
+        assert_eq!(reconciliation.sold, 3);
+        assert_eq!(reconciliation.expected, 17);
+        assert_eq!(reconciliation.actual, 15);
+        assert_eq!(reconciliation.difference, -2);
+        assert_eq!(reconciliation.corrected, -2);
+        assert_eq!(reconciliation.unexplained, 0);
+    }
+
+    #[test]
+    fn logs_the_opening_stock_of_a_loaded_file() {
+        let mut shop = Shop::new(&[1]);
+        load_text(&mut shop, "5\n1\n0\n2\n0\n").unwrap();
+
+        let report = shop.stock_log().reconcile(shop.inventory());
+
+        assert_eq!(report[DEFAULT_PRODUCT].opening, 5);
+        assert_eq!(report[DEFAULT_PRODUCT].unexplained, 0);
+    }
+
+    fn customer(first_name: &str, priority: PriorityLevel) -> Customer {
+        Customer::new(first_name.to_string(), "Doe".to_string(), 1).with_priority(priority)
+    }
+
+    fn first_names<'a>(customers: impl IntoIterator<Item = &'a Customer>) -> Vec<&'a str> {
+        customers
+            .into_iter()
+            .map(|customer| customer.first_name())
+            .collect()
+    }
+
+    #[test]
+    fn promotes_the_highest_priority_and_undoes_it() {
+        let mut shop = Shop::new(&[1]).with_stock(10);
+        shop.add_customer(customer("Ann", PriorityLevel::Regular))
+            .unwrap();
+        shop.add_customer(customer("Bob", PriorityLevel::Regular))
+            .unwrap();
+        shop.add_customer(customer("Cat", PriorityLevel::Priority))
+            .unwrap();
+        shop.add_customer(customer("Dan", PriorityLevel::Regular))
+            .unwrap();
+
+        shop.serve_customer(0).unwrap();
+
+        assert_eq!(first_names(shop.view_data()[0].view_data()), ["Cat"]);
+        assert_eq!(first_names(shop.waiting_list()), ["Bob", "Dan"]);
+
+        shop.undo().unwrap();
+
+        assert_eq!(first_names(shop.view_data()[0].view_data()), ["Ann"]);
+        assert_eq!(first_names(shop.waiting_list()), ["Bob", "Cat", "Dan"]);
+    }
+
+    #[test]
+    fn stops_overtaking_a_customer_after_the_limit() {
+        let mut shop = Shop::new(&[5]);
+        shop.add_customer(customer("Ann", PriorityLevel::Regular))
+            .unwrap();
+
+        for first_name in ["Bob", "Cat", "Dan", "Eve"] {
+            shop.add_customer(customer(first_name, PriorityLevel::Priority))
+                .unwrap();
+        }
+
+        assert_eq!(
+            first_names(shop.view_data()[0].view_data()),
+            ["Bob", "Cat", "Dan", "Ann", "Eve"]
+        );
+        assert_eq!(
+            shop.view_data()[0].view_data()[3].overtaken(),
+            MAX_OVERTAKES
+        );
+    }
+
+    #[test]
+    fn moving_a_customer_clears_their_overtakes() {
+        let mut shop = Shop::new(&[2, 2]);
+        shop.add_customer_to(0, customer("Ann", PriorityLevel::Regular))
+            .unwrap();
+        shop.add_customer_to(0, customer("Bob", PriorityLevel::Staff))
+            .unwrap();
+        assert_eq!(shop.view_data()[0].view_data()[1].overtaken(), 1);
+
+        let moved = shop.move_customer(0, 1, 1).unwrap();
+
+        assert_eq!(moved.first_name(), "Ann");
+        assert_eq!(moved.overtaken(), 0);
+    }
+
+    #[test]
+    fn rejects_a_priority_count_that_does_not_match() {
+        let mut shop = Shop::new(&[2]);
+        shop.add_customer(customer("Ann", PriorityLevel::Regular))
+            .unwrap();
+        let data = shop.to_string().replace("\n1\nregular\n", "\n2\nregular\n");
+
+        let result = load_text(&mut Shop::new(&[1]), &data);
+
+        assert!(matches!(
+            result,
+            Err(LoadError::InvalidField {
+                field: LoadField::PriorityCount,
+                kind: LoadErrorKind::CustomerCountMismatch(1),
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn rejects_a_customer_id_count_that_does_not_match() {
+        let mut shop = Shop::new(&[2]);
+        shop.add_customer(customer("Ann", PriorityLevel::Regular))
+            .unwrap();
+        let id = shop.view_data()[0].view_data()[0].id();
+        let data = shop
+            .to_string()
+            .replace(&format!("\n1\n{id}\n"), &format!("\n2\n{id}\n"));
+
+        let result = load_text(&mut Shop::new(&[1]), &data);
+
+        assert!(matches!(
+            result,
+            Err(LoadError::InvalidField {
+                field: LoadField::CustomerIdCount,
+                kind: LoadErrorKind::CustomerCountMismatch(1),
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn rejects_the_largest_customer_id() {
+        let mut shop = Shop::new(&[2]);
+        shop.add_customer(customer("Ann", PriorityLevel::Regular))
+            .unwrap();
+        let id = shop.view_data()[0].view_data()[0].id();
+        let data = shop
+            .to_string()
+            .replace(&format!("\n1\n{id}\n"), &format!("\n1\n{}\n", u64::MAX));
+
+        let result = load_text(&mut Shop::new(&[1]), &data);
+
+        let kind = assert_invalid_field(result, data.lines().count() - 1, LoadField::CustomerId);
+        assert!(matches!(
+            kind,
+            LoadErrorKind::CustomerIdTooLarge(CustomerId(u64::MAX))
+        ));
+    }
+
+    #[test]
+    fn leaves_the_shop_untouched_on_failure() {
+        let mut shop = Shop::new(&[2, 3]).with_stock(7);
+        shop.add_customer(Customer::new("Jane".to_string(), "Doe".to_string(), 2))
+            .unwrap();
+        let before = shop.to_string();
+
+        // The queues read fine, the broken field only comes at the very end
+        let result = load_text(&mut shop, "5\n1\n0\n2\n1\nJohn\nRoe\n1\nmany\n");
+
+        assert!(result.is_err());
+        assert_eq!(shop.to_string(), before);
+        assert!(shop.history().can_undo());
+    }
+
+    #[test]
+    fn redoes_a_serve_at_its_original_time() {
+        let mut shop = Shop::new(&[2]).with_stock(7);
+        shop.add_customer(Customer::new("Jane".to_string(), "Doe".to_string(), 2))
+            .unwrap();
+        shop.serve_customer(0).unwrap();
+        let served_at = shop.ledger().sales()[0].timestamp;
+
+        shop.undo().unwrap();
+        shop.redo().unwrap();
+
+        assert_eq!(shop.ledger().sales().len(), 1);
+        assert_eq!(shop.ledger().sales()[0].timestamp, served_at);
+    }
+
+    #[test]
+    fn moves_the_round_robin_on_only_for_customers_that_stay() {
+        let mut shop = Shop::with_strategy(&[2, 2, 2], Box::new(RoundRobin::default()));
+        shop.add_customer(customer("Ann", PriorityLevel::Regular))
+            .unwrap();
+
+        // The journal cannot be written, so the customer never joins queue 1
+        shop.set_journal(Some(Journal::new(
+            env::temp_dir().join("sdii_cw_missing_dir").join("journal"),
+        )));
+        assert!(shop
+            .add_customer(customer("Bob", PriorityLevel::Regular))
+            .is_err());
+        shop.set_journal(None);
+
+        shop.add_customer(customer("Bob", PriorityLevel::Regular))
+            .unwrap();
+        shop.undo().unwrap();
+        shop.add_customer(customer("Cat", PriorityLevel::Regular))
+            .unwrap();
+
+        assert_eq!(first_names(shop.view_data()[1].view_data()), ["Cat"]);
+        assert!(shop.view_data()[2].is_empty());
+
+        shop.undo().unwrap();
+        shop.redo().unwrap();
+        shop.add_customer(customer("Dan", PriorityLevel::Regular))
+            .unwrap();
+
+        assert_eq!(first_names(shop.view_data()[2].view_data()), ["Dan"]);
+    }
+
+    #[test]
+    fn replays_the_partial_serve_policy() {
+        let path = env::temp_dir().join(format!("sdii_cw_policy_journal_{}", std::process::id()));
+        let mut shop = Shop::new(&[2]);
+        shop.set_journal(Some(Journal::new(&path)));
+        shop.set_partial_serve_policy(PartialServePolicy::KeepRemaining)
+            .unwrap();
+
+        let entries = Journal::new(&path).read_entries().unwrap();
+        let mut replayed = Shop::new(&[2]);
+        replayed.replay_journal(&entries).unwrap();
+
+        assert_eq!(
+            replayed.partial_serve_policy(),
+            PartialServePolicy::KeepRemaining
+        );
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn discards_a_failed_transaction() {
+        let path = env::temp_dir().join(format!("sdii_cw_journal_test_{}", std::process::id()));
+        let mut shop = Shop::new(&[2, 3]).with_stock(7);
+        shop.add_customer(Customer::new("Jane".to_string(), "Doe".to_string(), 2))
+            .unwrap();
+        shop.set_journal(Some(Journal::new(&path)));
+
+        let events = std::rc::Rc::new(std::cell::Cell::new(0));
+        let counter = events.clone();
+        shop.subscribe(move |_, _| counter.set(counter.get() + 1));
+        let before = shop.to_string();
+
+        // The first step works but the second one cannot, so neither may stick
+        let result = shop.transaction(|shop| {
+            shop.take_customer(0, 0)?;
+            shop.take_customer(0, 0)
+        });
+
+        assert!(result.is_err());
+        assert_eq!(shop.to_string(), before);
+        assert_eq!(events.get(), 0);
+        assert!(Journal::new(&path).read_entries().unwrap().is_empty());
+
+        shop.move_customer(0, 0, 1).unwrap();
+
+        assert_eq!(shop.view_data()[1].len(), 1);
+        assert!(events.get() > 0);
+        assert_eq!(Journal::new(&path).read_entries().unwrap().len(), 2);
+
+        fs::remove_file(&path).unwrap();
     }
 }
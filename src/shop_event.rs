@@ -0,0 +1,67 @@
+use std::{fmt::Debug, rc::Rc};
+
+use crate::shop::{Shop, StockWarning};
+
+/// Something that changed in a shop. Subscribers get the shop itself along with the event so they
+/// can redraw just the part that changed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ShopEvent {
+    CustomerAdded { queue_no: usize },
+    CustomerRemoved { queue_no: usize },
+    CustomerServed { queue_no: usize },
+    SaleRefunded { queue_no: usize },
+    StockChanged { product: String, stock: usize },
+    ProductsChanged,
+    StockLow(StockWarning),
+    WaitingListChanged,
+    QueuesReloaded,
+}
+
+pub type SubscriptionId = usize;
+
+type Subscriber = Rc<dyn Fn(&Shop, &ShopEvent)>;
+
+#[derive(Default)]
+pub struct EventBus {
+    next_id: SubscriptionId,
+    subscribers: Vec<(SubscriptionId, Subscriber)>,
+}
+
+impl Debug for EventBus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EventBus")
+            .field("subscribers", &self.subscribers.len())
+            .finish()
+    }
+}
+
+/// Subscribers belong to the shop they subscribed to, so a clone starts without any.
+impl Clone for EventBus {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+
+impl EventBus {
+    pub fn subscribe(
+        &mut self,
+        subscriber: impl Fn(&Shop, &ShopEvent) + 'static,
+    ) -> SubscriptionId {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.subscribers.push((id, Rc::new(subscriber)));
+
+        id
+    }
+
+    pub fn unsubscribe(&mut self, id: SubscriptionId) {
+        self.subscribers
+            .retain(|(subscriber_id, _)| *subscriber_id != id);
+    }
+
+    pub fn emit(&self, shop: &Shop, event: ShopEvent) {
+        for (_, subscriber) in self.subscribers.iter() {
+            subscriber(shop, &event);
+        }
+    }
+}
@@ -0,0 +1,238 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    io::{self, Write},
+};
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::inventory::{Inventory, Product};
+
+pub const DEFAULT_EXPORT_PATH: &str = "./stock_log.csv";
+
+/// Why the stock of a product changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StockReason {
+    /// The stock a product started with when it was added or loaded, which reconciliation counts
+    /// from.
+    Opening,
+    Delivery,
+    Correction,
+    Wastage,
+    Sale,
+}
+
+impl StockReason {
+    pub const ALL: [StockReason; 5] = [
+        StockReason::Opening,
+        StockReason::Delivery,
+        StockReason::Correction,
+        StockReason::Wastage,
+        StockReason::Sale,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            StockReason::Opening => "opening",
+            StockReason::Delivery => "delivery",
+            StockReason::Correction => "correction",
+            StockReason::Wastage => "wastage",
+            StockReason::Sale => "sale",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|reason| reason.name().eq_ignore_ascii_case(name.trim()))
+    }
+}
+
+impl Display for StockReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A single change to the stock of a product. `quantity` is negative when stock was taken out,
+/// and `stock` is what the product was left with.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StockChange {
+    pub product: String,
+    pub reason: StockReason,
+    pub quantity: isize,
+    pub stock: usize,
+    pub timestamp: DateTime<Local>,
+    pub operator: String,
+}
+
+impl Display for StockChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "{}\n{}\n{}\n{}\n{}\n{}",
+            self.product,
+            self.reason,
+            self.quantity,
+            self.stock,
+            self.timestamp.to_rfc3339(),
+            self.operator
+        ))
+    }
+}
+
+/// How the logged changes of a product add up against its actual stock.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Reconciliation {
+    /// Stock the product was added or loaded with.
+    pub opening: usize,
+    pub delivered: isize,
+    pub sold: isize,
+    pub wasted: isize,
+    /// The opening stock with deliveries, sales and wastage applied.
+    pub expected: isize,
+    pub actual: usize,
+    /// Positive when there is more stock than expected.
+    pub difference: isize,
+    /// The part of the difference explained by logged corrections.
+    pub corrected: isize,
+    /// The part of the difference nothing in the log accounts for.
+    pub unexplained: isize,
+}
+
+/// Every stock change the shop has made, oldest first. Changes are never removed, undoing one
+/// logs the reverse change instead.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct StockLog {
+    changes: Vec<StockChange>,
+}
+
+impl StockLog {
+    pub fn changes(&self) -> &[StockChange] {
+        self.changes.as_slice()
+    }
+
+    pub fn record(&mut self, change: StockChange) {
+        self.changes.push(change);
+    }
+
+    /// Logs the stock a product starts with.
+    pub fn record_opening(&mut self, product: &Product, operator: &str) {
+        self.record(StockChange {
+            product: product.name().to_string(),
+            reason: StockReason::Opening,
+            quantity: product.stock() as isize,
+            stock: product.stock(),
+            timestamp: Local::now(),
+            operator: operator.to_string(),
+        });
+    }
+
+    /// Gives every product without an opening entry one, used after loading. Products that
+    /// already have changes from before openings were logged open with the stock they had before
+    /// the first of them, the others with their current stock.
+    pub fn record_missing_openings(&mut self, inventory: &Inventory, operator: &str) {
+        for product in inventory.products() {
+            let mut changes = self
+                .changes
+                .iter()
+                .enumerate()
+                .filter(|(_, change)| change.product == product.name());
+
+            if changes
+                .clone()
+                .any(|(_, change)| change.reason == StockReason::Opening)
+            {
+                continue;
+            }
+
+            match changes.next() {
+                Some((change_no, change)) => {
+                    let stock = (change.stock as isize - change.quantity).max(0) as usize;
+                    let opening = StockChange {
+                        product: change.product.clone(),
+                        reason: StockReason::Opening,
+                        quantity: stock as isize,
+                        stock,
+                        timestamp: change.timestamp,
+                        operator: change.operator.clone(),
+                    };
+
+                    self.changes.insert(change_no, opening);
+                }
+                None => self.record_opening(product, operator),
+            }
+        }
+    }
+
+    /// Writes the log as CSV with a header row.
+    pub fn export_csv(&self, mut output: impl Write) -> io::Result<()> {
+        writeln!(output, "timestamp,product,reason,quantity,stock,operator")?;
+
+        for change in self.changes.iter() {
+            writeln!(
+                output,
+                "{},{},{},{},{},{}",
+                change.timestamp.to_rfc3339(),
+                csv_field(&change.product),
+                change.reason,
+                change.quantity,
+                change.stock,
+                csv_field(&change.operator)
+            )?;
+        }
+
+        output.flush()
+    }
+
+    /// Compares what the log says each product should hold against its actual stock.
+    pub fn reconcile(&self, inventory: &Inventory) -> BTreeMap<String, Reconciliation> {
+        let mut report = BTreeMap::new();
+
+        for product in inventory.products() {
+            let changes = self
+                .changes
+                .iter()
+                .filter(|change| change.product == product.name())
+                .collect::<Vec<_>>();
+
+            let mut reconciliation = Reconciliation {
+                actual: product.stock(),
+                ..Default::default()
+            };
+
+            for change in changes {
+                match change.reason {
+                    StockReason::Opening => reconciliation.opening += change.stock,
+                    StockReason::Delivery => reconciliation.delivered += change.quantity,
+                    StockReason::Sale => reconciliation.sold -= change.quantity,
+                    StockReason::Wastage => reconciliation.wasted -= change.quantity,
+                    StockReason::Correction => reconciliation.corrected += change.quantity,
+                }
+            }
+
+            // Corrections are left out of the expected stock so they show up as explaining the
+            // difference rather than hiding it
+            reconciliation.expected = reconciliation.opening as isize + reconciliation.delivered
+                - reconciliation.sold
+                - reconciliation.wasted;
+            reconciliation.difference = reconciliation.actual as isize - reconciliation.expected;
+            reconciliation.unexplained = reconciliation.difference - reconciliation.corrected;
+
+            report.insert(product.name().to_string(), reconciliation);
+        }
+
+        report
+    }
+}
+
+/// Quotes a CSV field when it holds a comma, quote or line break.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
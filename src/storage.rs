@@ -0,0 +1,278 @@
+use std::{
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use crate::shop::{SaveFormat, Shop};
+
+pub const DATA_DIRECTORY: &str = "./saves";
+pub const SLOT_EXTENSION: &str = "json";
+pub const BACKUP_DIRECTORY: &str = ".backups";
+pub const BACKUP_COUNT: usize = 3;
+
+#[derive(Debug)]
+pub struct SaveInfo {
+    pub name: String,
+    pub path: PathBuf,
+    pub modified: Option<SystemTime>,
+    pub counts: Option<(usize, usize)>,
+}
+
+impl SaveInfo {
+    /// Reads the metadata of a save file, loading it to count its queues and customers, including
+    /// those on the waiting list.
+    pub fn read(name: String, path: PathBuf) -> io::Result<Self> {
+        let modified = fs::metadata(&path)?.modified().ok();
+
+        let mut shop = Shop::default();
+        let counts = shop
+            .load(&mut File::open(&path)?, SaveFormat::from_path(&path))
+            .ok()
+            .map(|_| {
+                let customer_count = shop
+                    .view_data()
+                    .iter()
+                    .map(|queue| queue.len())
+                    .sum::<usize>()
+                    + shop.waiting_list().len();
+                (shop.len(), customer_count)
+            });
+
+        Ok(Self {
+            name,
+            path,
+            modified,
+            counts,
+        })
+    }
+}
+
+/// Turns user input into a save path. Input that looks like a path is used as is, anything else is
+/// treated as the name of a slot inside the data directory.
+pub fn resolve_save_path(input: &str) -> PathBuf {
+    let path = Path::new(input);
+
+    if path.extension().is_some() || path.components().count() > 1 {
+        path.to_path_buf()
+    } else {
+        slot_path(input)
+    }
+}
+
+pub fn slot_path(name: &str) -> PathBuf {
+    Path::new(DATA_DIRECTORY).join(format!("{name}.{SLOT_EXTENSION}"))
+}
+
+/// Makes sure the directory a save file will be written to exists.
+pub fn prepare_save_path(path: &Path) -> io::Result<()> {
+    fs::create_dir_all(parent_directory(path))
+}
+
+/// Saves the shop without ever leaving a half written file behind. The data is written to a
+/// temporary file which is synced and then renamed over the old save, after the old save has been
+/// copied into the backup rotation.
+pub fn write_save(path: &Path, shop: &Shop) -> io::Result<()> {
+    prepare_save_path(path)?;
+
+    let temp_path = temp_path(path);
+    let mut temp_file = File::create(&temp_path)?;
+
+    if let Err(error) = shop
+        .save(&mut temp_file, SaveFormat::from_path(path))
+        .and_then(|_| temp_file.sync_all())
+    {
+        let _ = fs::remove_file(&temp_path);
+        return Err(error);
+    }
+    drop(temp_file);
+
+    rotate_backups(path)?;
+    fs::rename(&temp_path, path)?;
+
+    // Persist the rename itself, not every platform can sync a directory so this is best effort
+    if let Ok(directory) = File::open(parent_directory(path)) {
+        let _ = directory.sync_all();
+    }
+
+    Ok(())
+}
+
+/// Backups live in a hidden directory next to the save and keep its extension so the format can
+/// still be detected, e.g. `./saves/.backups/monday.1.json`.
+pub fn backup_path(path: &Path, backup_no: usize) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let file_name = match path.extension() {
+        Some(extension) => format!("{stem}.{backup_no}.{}", extension.to_string_lossy()),
+        None => format!("{stem}.{backup_no}"),
+    };
+
+    parent_directory(path)
+        .join(BACKUP_DIRECTORY)
+        .join(file_name)
+}
+
+/// Lists the backups that exist, newest first. They are named by their place in the list, so
+/// `Backup 1` is always the first one even when a backup file has gone missing.
+pub fn list_backups(path: &Path) -> io::Result<Vec<SaveInfo>> {
+    let mut backups = Vec::new();
+
+    for backup_no in 1..=BACKUP_COUNT {
+        let backup_path = backup_path(path, backup_no);
+
+        if backup_path.exists() {
+            let name = format!("Backup {}", backups.len() + 1);
+            backups.push(SaveInfo::read(name, backup_path)?);
+        }
+    }
+
+    Ok(backups)
+}
+
+fn rotate_backups(path: &Path) -> io::Result<()> {
+    if !path.exists() {
+        return Ok(());
+    }
+
+    fs::create_dir_all(parent_directory(path).join(BACKUP_DIRECTORY))?;
+
+    for backup_no in (1..BACKUP_COUNT).rev() {
+        let backup_path = backup_path(path, backup_no);
+
+        if backup_path.exists() {
+            fs::rename(&backup_path, self::backup_path(path, backup_no + 1))?;
+        }
+    }
+
+    fs::copy(path, backup_path(path, 1))?;
+
+    Ok(())
+}
+
+fn temp_path(path: &Path) -> PathBuf {
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    parent_directory(path).join(format!(".{file_name}.tmp"))
+}
+
+fn parent_directory(path: &Path) -> &Path {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    }
+}
+
+pub fn list_slots() -> io::Result<Vec<SaveInfo>> {
+    let entries = match fs::read_dir(DATA_DIRECTORY) {
+        Ok(entries) => entries,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(error) => return Err(error),
+    };
+
+    let mut slots = Vec::new();
+
+    for entry in entries {
+        let path = entry?.path();
+
+        if path.extension().and_then(|extension| extension.to_str()) != Some(SLOT_EXTENSION) {
+            continue;
+        }
+
+        let name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        slots.push(SaveInfo::read(name, path)?);
+    }
+
+    slots.sort_by(|slot1, slot2| slot1.name.cmp(&slot2.name));
+
+    Ok(slots)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+    use crate::customer::Customer;
+
+    /// A shop with one queue holding as many customers as the queue has room for, with the rest
+    /// on the waiting list.
+    fn shop(no_customers: usize) -> Shop {
+        let mut shop = Shop::new(&[2]).with_stock(10);
+
+        for _ in 0..no_customers {
+            shop.add_customer(Customer::new("Jane".to_string(), "Doe".to_string(), 1))
+                .unwrap();
+        }
+
+        shop
+    }
+
+    fn customer_count(save: &SaveInfo) -> usize {
+        save.counts.unwrap().1
+    }
+
+    #[test]
+    fn keeps_the_latest_saves_as_backups() {
+        let directory = env::temp_dir().join(format!("sdii_cw_backups_{}", std::process::id()));
+        let path = directory.join("monday.json");
+
+        for no_customers in 0..=BACKUP_COUNT + 1 {
+            write_save(&path, &shop(no_customers)).unwrap();
+
+            // Every save replaces the file through the temporary one
+            assert!(!temp_path(&path).exists());
+            assert_eq!(
+                customer_count(&SaveInfo::read(String::new(), path.clone()).unwrap()),
+                no_customers
+            );
+        }
+
+        let backups = list_backups(&path).unwrap();
+        assert_eq!(
+            backups.iter().map(customer_count).collect::<Vec<_>>(),
+            [BACKUP_COUNT, BACKUP_COUNT - 1, BACKUP_COUNT - 2]
+        );
+        assert!(!backup_path(&path, BACKUP_COUNT + 1).exists());
+
+        // The backups after a missing one move up in the list
+        fs::remove_file(backup_path(&path, 1)).unwrap();
+        let backups = list_backups(&path).unwrap();
+
+        assert_eq!(backups.len(), BACKUP_COUNT - 1);
+        assert_eq!(backups[0].name, "Backup 1");
+        assert_eq!(backups[0].path, backup_path(&path, 2));
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn leaves_the_old_save_when_writing_fails() {
+        let directory = env::temp_dir().join(format!("sdii_cw_failed_save_{}", std::process::id()));
+        let path = directory.join("monday.json");
+        write_save(&path, &shop(1)).unwrap();
+
+        // A directory where the temporary file should go stops it from being created
+        fs::create_dir(temp_path(&path)).unwrap();
+        assert!(write_save(&path, &shop(2)).is_err());
+
+        assert_eq!(
+            customer_count(&SaveInfo::read(String::new(), path.clone()).unwrap()),
+            1
+        );
+        assert!(list_backups(&path).unwrap().is_empty());
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
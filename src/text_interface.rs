@@ -1,93 +1,169 @@
 use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    fmt::Display,
     fs::File,
-    io::{stdin, stdout, Write},
+    io::{self, stdin, stdout, BufRead, StdinLock, Stdout, Write},
+    path::{Path, PathBuf},
+    rc::Rc,
 };
 
 use adw::prelude::*;
+use chrono::{DateTime, Local};
+use gtk::gio;
 
 use crate::{
-    customer::Customer,
+    customer::{Customer, LineItem, PriorityLevel},
+    customer_search::{self, MatchMode, SearchField, SearchOptions},
     food_queue::{FoodQueue, FoodQueueError},
     gui_window::GuiWindow,
-    shop::{self, Shop, ShopError},
+    history::HistoryEntry,
+    inventory::{Product, DEFAULT_PRODUCT},
+    journal::JournalEntry,
+    ledger::Revenue,
+    queue_strategy,
+    shop::{self, CustomerPlacement, LoadError, PartialServePolicy, SaveFormat, Shop, ShopError},
+    stock_log::{self, StockReason},
+    storage::{self, SaveInfo},
 };
 
 const DECOR_CHARACTER: &'static str = "*";
 const DECOR_PADDING: usize = 10;
-const DEFAULT_SAVE_PATH: &'static str = "./program_state.txt";
+const DEFAULT_SAVE_PATH: &'static str = "./program_state.json";
+const LEGACY_SAVE_PATH: &'static str = "./program_state.txt";
 const APP_ID: &'static str = "com.github.abrarsl.sdii_cw_class_rs";
 
-pub struct TextInterface {
-    shop: Shop,
-    longest_queue_length: usize,
+/// The text interface reads commands from `input` and writes everything to `output`, which are
+/// the terminal unless other streams are given to `with_io`.
+pub struct TextInterface<R = StdinLock<'static>, W = Stdout> {
+    shop: Rc<RefCell<Shop>>,
+    input: R,
+    output: W,
 }
 
 #[derive(Debug)]
 pub enum InputError {
-    IOError,
+    IOError(io::Error),
+    EndOfInput,
     InputTypeError,
     InputRangeError(isize, isize),
 }
 
 impl TextInterface {
     pub fn new(shop: Shop) -> Self {
-        let longest_queue_length = shop
-            .view_data()
-            .iter()
-            .max_by(|queue1, queue2| queue1.capacity().cmp(&queue2.capacity()))
-            .unwrap()
-            .capacity();
+        Self::with_io(shop, stdin().lock(), stdout())
+    }
+}
 
+impl<R: BufRead, W: Write> TextInterface<R, W> {
+    pub fn with_io(shop: Shop, input: R, output: W) -> Self {
         Self {
-            shop,
-            longest_queue_length,
+            shop: Rc::new(RefCell::new(shop)),
+            input,
+            output,
+        }
+    }
+
+    /// Runs commands until `EXT` or the end of the input. An error is only returned when the
+    /// input or output stops working.
+    pub fn run(&mut self) -> io::Result<()> {
+        match self.command_loop() {
+            Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => writeln!(self.output)?,
+            result => result?,
         }
+
+        writeln!(self.output, "Exiting Text Interface...")
     }
 
-    pub fn run(&mut self) {
-        Self::display_commands();
+    fn command_loop(&mut self) -> io::Result<()> {
+        self.display_commands()?;
 
         loop {
-            match Self::string_input_prompt("Enter a command: ")
-                .unwrap()
-                .to_uppercase()
-                .trim()
-            {
-                "VFQ" => self.vfq(),
-                "VEQ" => self.veq(),
-                "ACQ" => self.acq(),
-                "RCQ" => self.rcq(),
-                "PCQ" => self.pcq(),
-                "VCS" => self.vcs(),
-                "SPD" => self.spd(),
-                "LPD" => self.lpd(),
-                "STK" => self.stk(),
-                "AFS" => self.afs(),
-                "GUI" => self.gui(),
-                "HHH" => Self::display_commands(),
-                "EXT" => break,
-                _ => println!("Unknown Command!"),
+            let command = match self.string_input_prompt("Enter a command: ") {
+                Ok(command) => command,
+                Err(InputError::EndOfInput) => return Ok(()),
+                Err(error) => return self.handle_input_error(error),
+            };
+
+            match command.to_uppercase().trim() {
+                "VFQ" => self.vfq()?,
+                "VEQ" => self.veq()?,
+                "ACQ" => self.acq()?,
+                "RCQ" => self.rcq()?,
+                "PCQ" => self.pcq()?,
+                "VCS" => self.vcs()?,
+                "SCS" => self.scs()?,
+                "SPD" => self.spd()?,
+                "LPD" => self.lpd()?,
+                "LSD" => self.lsd()?,
+                "RBK" => self.rbk()?,
+                "STK" => self.stk()?,
+                "AFS" => self.afs()?,
+                "ADJ" => self.adj()?,
+                "VSL" => self.vsl()?,
+                "ESL" => self.esl()?,
+                "RSR" => self.rsr()?,
+                "UND" => self.und()?,
+                "RED" => self.red()?,
+                "QAS" => self.qas()?,
+                "PSP" => self.psp()?,
+                "APR" => self.apr()?,
+                "RPQ" => self.rpq()?,
+                "RPD" => self.rpd()?,
+                "RPC" => self.rpc()?,
+                "GUI" => self.gui()?,
+                "HHH" => self.display_commands()?,
+                "EXT" => return Ok(()),
+                _ => writeln!(self.output, "Unknown Command!")?,
             }
         }
-
-        println!("Exiting Text Interface...");
     }
 
-    fn string_input_prompt(prompt: &str) -> Result<String, InputError> {
+    fn string_input_prompt(&mut self, prompt: &str) -> Result<String, InputError> {
         let mut text_buffer = String::new();
 
-        print!("{prompt}");
-        stdout().flush().or_else(|_| Err(InputError::IOError))?;
+        write!(self.output, "{prompt}").map_err(InputError::IOError)?;
+        self.output.flush().map_err(InputError::IOError)?;
 
-        stdin()
+        let bytes_read = self
+            .input
             .read_line(&mut text_buffer)
-            .or_else(|_| Err(InputError::IOError))?;
+            .map_err(InputError::IOError)?;
+
+        if bytes_read == 0 {
+            return Err(InputError::EndOfInput);
+        }
 
         Ok(text_buffer.trim().to_string())
     }
 
-    fn int_input_prompt(prompt: &str, start: isize, end: isize) -> Result<isize, InputError> {
-        let number = Self::string_input_prompt(prompt)?
+    fn int_input_prompt(
+        &mut self,
+        prompt: &str,
+        start: isize,
+        end: isize,
+    ) -> Result<isize, InputError> {
+        Self::parse_int_input(&self.string_input_prompt(prompt)?, start, end)
+    }
+
+    /// Like `int_input_prompt` but an empty answer is accepted and returned as `None`.
+    fn optional_int_input_prompt(
+        &mut self,
+        prompt: &str,
+        start: isize,
+        end: isize,
+    ) -> Result<Option<isize>, InputError> {
+        let input = self.string_input_prompt(prompt)?;
+
+        if input.is_empty() {
+            Ok(None)
+        } else {
+            Self::parse_int_input(&input, start, end).map(Some)
+        }
+    }
+
+    fn parse_int_input(input: &str, start: isize, end: isize) -> Result<isize, InputError> {
+        let number = input
             .trim()
             .parse()
             .or_else(|_| Err(InputError::InputTypeError))?;
@@ -99,90 +175,198 @@ impl TextInterface {
         }
     }
 
-    fn display_commands() {
-        Self::display_header("Commands");
+    fn display_commands(&mut self) -> io::Result<()> {
+        self.display_header("Commands")?;
 
-        println!(
+        writeln!(
+            self.output,
             r"VFQ => View all queues.
 VEQ => View empty queues.
 ACQ => Add customer to queue.
 RCQ => Remove customer from queue.
 PCQ => Server customer from queue.
 VCS => View sorted customers.
+SCS => Search for customers.
 SPD => Save program data.
 LPD => Load program data.
+LSD => List saved data.
+RBK => Restore from backup.
 STK => View stock info.
 AFS => Add items to stock.
+ADJ => Adjust stock.
+VSL => View stock log.
+ESL => Export stock log.
+RSR => Reconcile stock.
+APR => Add product to catalog.
+UND => Undo last operation.
+RED => Redo last undone operation.
+QAS => Change queue assignment strategy.
+PSP => Change partial serve policy.
+RPQ => View revenue per queue.
+RPD => View revenue per day.
+RPC => View revenue per customer.
 GUI => Launch GUI.
 HHH => Display help.
 EXT => Exit program."
-        );
+        )
     }
 
-    fn display_header(title: &str) {
+    fn display_header(&mut self, title: &str) -> io::Result<()> {
         let horizontal_decor = DECOR_CHARACTER.repeat(title.len() + DECOR_PADDING);
         let side_padding = " ".repeat((DECOR_PADDING - 2) / 2);
 
-        println!(
+        writeln!(
+            self.output,
             "{}\n*{}{}{}*\n{}",
             horizontal_decor, side_padding, title, side_padding, horizontal_decor
-        );
+        )
     }
 
-    fn display_queues(&self, title: &str, queues: &[FoodQueue]) {
-        Self::display_header(title);
+    fn display_queues(&mut self, title: &str, queues: &[FoodQueue]) -> io::Result<()> {
+        self.display_header(title)?;
 
-        let queue_char_padding =
-            " ".repeat(((title.len() + DECOR_PADDING - queues.len()) / queues.len()) / 2);
+        if queues.is_empty() {
+            return writeln!(self.output, "No queues to display.");
+        }
 
-        for i in 0..self.longest_queue_length {
-            for j in 0..queues.len() {
-                let mut char = "X";
+        // Queues that no longer fit under the header are packed together without padding
+        let queue_char_padding = " ".repeat(
+            ((title.len() + DECOR_PADDING).saturating_sub(queues.len()) / queues.len()) / 2,
+        );
 
-                if i >= queues[j].capacity() {
-                    char = " ";
-                } else if i >= queues[j].view_data().len() {
-                    char = "O";
-                }
+        // Loading can change the layout, so the rows are counted from the shop every time
+        let longest_queue_length = self
+            .shop
+            .borrow()
+            .view_data()
+            .iter()
+            .map(|queue| queue.capacity())
+            .max()
+            .unwrap_or(0);
+
+        for i in 0..longest_queue_length {
+            for j in 0..queues.len() {
+                // Customers ahead of others are marked with the initial of their priority
+                let char = match queues[j].view_data().get(i) {
+                    Some(customer) if customer.priority() != PriorityLevel::Regular => {
+                        customer.priority().initial()
+                    }
+                    Some(_) => "X",
+                    None if i >= queues[j].capacity() => " ",
+                    None => "O",
+                };
 
-                print!("{queue_char_padding}{char}{queue_char_padding}");
+                write!(
+                    self.output,
+                    "{queue_char_padding}{char}{queue_char_padding}"
+                )?;
             }
-            print!("\n");
+            write!(self.output, "\n")?;
         }
 
-        stdout().flush().unwrap();
+        self.output.flush()
     }
 
-    fn handle_input_error(error: InputError) {
+    /// Reports bad input. Running out of input or failing to read it is returned as an error
+    /// since nothing more can be done with the interface.
+    fn handle_input_error(&mut self, error: InputError) -> io::Result<()> {
         match error {
-            InputError::IOError => println!("An unrecoverable system error has occured!"),
-            InputError::InputRangeError(start, end) => {
-                println!("Input is out of range! Input must be between {start} and {end}")
+            InputError::IOError(error) => Err(error),
+            InputError::EndOfInput => {
+                writeln!(
+                    self.output,
+                    "\nInput ended before the command was complete!"
+                )?;
+                Err(io::ErrorKind::UnexpectedEof.into())
             }
-            InputError::InputTypeError => println!("Input must be a valid number!"),
+            InputError::InputRangeError(start, end) => writeln!(
+                self.output,
+                "Input is out of range! Input must be between {start} and {end}"
+            ),
+            InputError::InputTypeError => writeln!(self.output, "Input must be a valid number!"),
         }
     }
 
-    fn handle_shop_error(error: ShopError) {
+    fn handle_shop_error(&mut self, error: ShopError) -> io::Result<()> {
         match error {
-            ShopError::Full => println!("All queues are full!"),
-            ShopError::QueueNotFound => println!("Queue not found!"),
-            ShopError::StockInsufficient => println!("Stock is insufficient to conduct operation!"),
-            ShopError::QueueError(queue_error) => match queue_error {
-                FoodQueueError::Full => println!("Queue is full!"),
-                FoodQueueError::Empty => println!("Queue is empty!"),
-                FoodQueueError::CustomerNotFound => println!("Customer not found!"),
-            },
+            ShopError::JournalError(error) => {
+                writeln!(
+                    self.output,
+                    "Failed to record the change, it was not applied!"
+                )?;
+                self.handle_io_error(error)
+            }
+            error => writeln!(self.output, "{error}"),
+        }
+    }
+
+    fn handle_io_error(&mut self, error: io::Error) -> io::Result<()> {
+        match error.kind() {
+            io::ErrorKind::NotFound => writeln!(self.output, "File not found!"),
+            io::ErrorKind::PermissionDenied => {
+                writeln!(self.output, "Permission denied to access the file!")
+            }
+            _ => writeln!(self.output, "File operation failed: {error}"),
+        }
+    }
+
+    fn handle_load_error(&mut self, error: LoadError) -> io::Result<()> {
+        match error {
+            LoadError::IOError(error) => self.handle_io_error(error),
+            error => writeln!(self.output, "{error}"),
         }
     }
 
-    fn vfq(&self) {
-        self.display_queues("View All The Queues", self.shop.view_data());
+    fn vfq(&mut self) -> io::Result<()> {
+        let shop = Rc::clone(&self.shop);
+        self.display_queues("View All The Queues", shop.borrow().view_data())?;
+        self.display_queue_income()?;
+        self.display_waiting_list()
     }
 
-    fn veq(&self) {
+    fn display_queue_income(&mut self) -> io::Result<()> {
+        let shop = Rc::clone(&self.shop);
+        let shop = shop.borrow();
+        let revenue = shop.ledger().by_queue();
+
+        for queue_no in 0..shop.len() {
+            let revenue = revenue.get(&queue_no).copied().unwrap_or_default();
+
+            writeln!(
+                self.output,
+                "Queue {queue_no} => Items served: {}, Income: {}",
+                revenue.no_items, revenue.revenue
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn display_waiting_list(&mut self) -> io::Result<()> {
+        let shop = Rc::clone(&self.shop);
+        let shop = shop.borrow();
+        let waiting_list = shop.waiting_list();
+
+        if waiting_list.is_empty() {
+            return writeln!(self.output, "No customers are waiting.");
+        }
+
+        writeln!(
+            self.output,
+            "Waiting list ({} customers):",
+            waiting_list.len()
+        )?;
+        for (waiting_pos, customer) in waiting_list.iter().enumerate() {
+            writeln!(self.output, "{waiting_pos} => {}", customer.full_name())?;
+        }
+
+        Ok(())
+    }
+
+    fn veq(&mut self) -> io::Result<()> {
         let queues = self
             .shop
+            .borrow()
             .view_data()
             .iter()
             .map(|queue| {
@@ -194,150 +378,1174 @@ EXT => Exit program."
             })
             .collect::<Vec<_>>();
 
-        self.display_queues("View Empty Queues", queues.as_slice());
+        self.display_queues("View Empty Queues", queues.as_slice())
     }
 
-    fn acq(&mut self) {
-        let first_name = Self::string_input_prompt("Enter first name: ").unwrap();
-        let last_name = Self::string_input_prompt("Enter last name: ").unwrap();
-        let no_items = match Self::int_input_prompt(
-            "Enter number of items: ",
-            1,
-            shop::STOCK_MAX_THRESHOLD as isize,
+    fn acq(&mut self) -> io::Result<()> {
+        let first_name = match self.string_input_prompt("Enter first name: ") {
+            Ok(value) => value,
+            Err(error) => return self.handle_input_error(error),
+        };
+        let last_name = match self.string_input_prompt("Enter last name: ") {
+            Ok(value) => value,
+            Err(error) => return self.handle_input_error(error),
+        };
+        let order = match self.order_prompt() {
+            Ok(value) => value,
+            Err(error) => return self.handle_input_error(error),
+        };
+
+        let queue_count = self.shop.borrow().len() as isize;
+        let queue_no = match self.optional_int_input_prompt(
+            "Enter the queue number (leave empty to pick automatically): ",
+            0,
+            queue_count - 1,
         ) {
+            Ok(value) => value.map(|value| value as usize),
+            Err(error) => return self.handle_input_error(error),
+        };
+
+        let priority = match self.priority_prompt() {
             Ok(value) => value,
-            Err(error) => {
-                Self::handle_input_error(error);
-                return;
-            }
+            Err(error) => return self.handle_input_error(error),
         };
 
-        let customer = Customer::new(first_name, last_name, no_items as usize);
+        let customer = Customer::with_order(first_name, last_name, order).with_priority(priority);
 
-        match self.shop.add_customer(customer) {
-            Ok(_) => println!("Successfully added to queue."),
-            Err(error) => Self::handle_shop_error(error),
+        let result = match queue_no {
+            Some(queue_no) => self
+                .shop
+                .borrow_mut()
+                .add_customer_to(queue_no, customer)
+                .map(|_| CustomerPlacement::Queue(queue_no)),
+            None => self.shop.borrow_mut().add_customer(customer),
         };
+
+        match result {
+            Ok(CustomerPlacement::Queue(queue_no)) => {
+                writeln!(self.output, "Successfully added to queue {queue_no}.")
+            }
+            Ok(CustomerPlacement::WaitingList(waiting_pos)) => writeln!(
+                self.output,
+                "All queues are full! Added to the waiting list at position {waiting_pos}."
+            ),
+            Err(error) => self.handle_shop_error(error),
+        }
     }
 
-    fn rcq(&mut self) {
-        let queue_no =
-            match Self::int_input_prompt("Enter the queue number: ", 0, self.shop.len() as isize) {
-                Ok(value) => value as usize,
-                Err(error) => {
-                    Self::handle_input_error(error);
-                    return;
+    fn priority_prompt(&mut self) -> Result<PriorityLevel, InputError> {
+        for (i, priority) in PriorityLevel::ALL.iter().enumerate() {
+            writeln!(self.output, "{i} => {}", priority.name()).map_err(InputError::IOError)?;
+        }
+
+        let priority_no = self.optional_int_input_prompt(
+            "Enter the priority (leave empty for regular): ",
+            0,
+            PriorityLevel::ALL.len() as isize - 1,
+        )?;
+
+        Ok(priority_no.map_or(PriorityLevel::default(), |value| {
+            PriorityLevel::ALL[value as usize]
+        }))
+    }
+
+    /// Asks for a quantity of every product, skipping the ones left at 0. With a single product
+    /// this is just the number of items.
+    fn order_prompt(&mut self) -> Result<Vec<LineItem>, InputError> {
+        let products = self.shop.borrow().inventory().products().to_vec();
+
+        if let [product] = products.as_slice() {
+            let quantity = self.int_input_prompt(
+                "Enter number of items: ",
+                1,
+                product.max_threshold() as isize,
+            )?;
+
+            return Ok(vec![LineItem {
+                product: product.name().to_string(),
+                quantity: quantity as usize,
+            }]);
+        }
+
+        loop {
+            let mut order = Vec::new();
+
+            for product in products.iter() {
+                let quantity = self.int_input_prompt(
+                    &format!("Enter number of {}: ", product.name()),
+                    0,
+                    product.max_threshold() as isize,
+                )?;
+
+                if quantity > 0 {
+                    order.push(LineItem {
+                        product: product.name().to_string(),
+                        quantity: quantity as usize,
+                    });
                 }
+            }
+
+            if !order.is_empty() {
+                return Ok(order);
+            }
+
+            writeln!(self.output, "An order needs at least one item!")
+                .map_err(InputError::IOError)?;
+        }
+    }
+
+    /// Lets the user pick a product, skipping the question when there is only one.
+    fn product_prompt(&mut self) -> Result<Product, InputError> {
+        let mut products = self.shop.borrow().inventory().products().to_vec();
+
+        if products.len() > 1 {
+            for (i, product) in products.iter().enumerate() {
+                writeln!(self.output, "{i} => {}", product.name()).map_err(InputError::IOError)?;
+            }
+        }
+
+        let product_no = match products.len() {
+            1 => 0,
+            len => {
+                self.int_input_prompt("Enter the product number: ", 0, len as isize - 1)? as usize
+            }
+        };
+
+        Ok(products.swap_remove(product_no))
+    }
+
+    fn rcq(&mut self) -> io::Result<()> {
+        let queue_count = self.shop.borrow().len() as isize;
+        let queue_no = match self.int_input_prompt("Enter the queue number: ", 0, queue_count - 1) {
+            Ok(value) => value as usize,
+            Err(error) => return self.handle_input_error(error),
+        };
+
+        let queue_length = self.shop.borrow().view_data()[queue_no].len() as isize;
+        if queue_length == 0 {
+            return self.handle_shop_error(ShopError::QueueError(FoodQueueError::Empty));
+        }
+
+        let customer_pos =
+            match self.int_input_prompt("Enter the customer position: ", 0, queue_length - 1) {
+                Ok(value) => value as usize,
+                Err(error) => return self.handle_input_error(error),
             };
 
-        let customer_pos = match Self::int_input_prompt(
-            "Enter the customer position: ",
-            0,
-            self.shop.view_data()[queue_no].len() as isize,
-        ) {
+        let result = self
+            .shop
+            .borrow_mut()
+            .remove_customer(queue_no, customer_pos);
+
+        match result {
+            Ok(customer) => writeln!(
+                self.output,
+                "Successfully removed customer {}",
+                customer.first_name()
+            ),
+            Err(error) => self.handle_shop_error(error),
+        }
+    }
+
+    fn pcq(&mut self) -> io::Result<()> {
+        let queue_count = self.shop.borrow().len() as isize;
+        let queue_no = match self.int_input_prompt("Enter the queue number: ", 0, queue_count - 1) {
             Ok(value) => value as usize,
+            Err(error) => return self.handle_input_error(error),
+        };
+
+        let result = self.shop.borrow_mut().serve_customer(queue_no);
+
+        match result {
+            Ok(outcome) => writeln!(self.output, "{outcome}")?,
+            Err(error) => return self.handle_shop_error(error),
+        }
+
+        let warnings = self.shop.borrow().stock_warnings();
+        for warning in warnings {
+            writeln!(self.output, "Warning: {warning}")?;
+        }
+
+        Ok(())
+    }
+
+    fn vcs(&mut self) -> io::Result<()> {
+        self.display_header("Sorted Customers List")?;
+
+        let shop = Rc::clone(&self.shop);
+        let shop = shop.borrow();
+        let sorted_customers = shop.get_sorted_customers();
+        for customer in sorted_customers {
+            writeln!(
+                self.output,
+                "ID: {}\nName: {}\nItems: {}\nPriority: {}",
+                customer.id(),
+                customer.full_name(),
+                customer.no_items(),
+                customer.priority()
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn scs(&mut self) -> io::Result<()> {
+        self.display_header("Search Customers")?;
+
+        let options = match self.search_options_prompt() {
+            Ok(value) => value,
+            Err(error) => return self.handle_input_error(error),
+        };
+
+        let shop = Rc::clone(&self.shop);
+        let shop = shop.borrow();
+        let results = shop.search(&options);
+        if results.is_empty() {
+            return writeln!(self.output, "No customers found.");
+        }
+
+        for result in results {
+            writeln!(
+                self.output,
+                "ID: {}\nName: {}\nItems: {}\nPriority: {}\nQueue: {}\nPosition: {}",
+                result.customer.id(),
+                result.customer.full_name(),
+                result.customer.no_items(),
+                result.customer.priority(),
+                result.queue_no,
+                result.customer_pos
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn search_options_prompt(&mut self) -> Result<SearchOptions, InputError> {
+        let mut options =
+            SearchOptions::new(&self.string_input_prompt(
+                "Enter a name to search for (leave empty to match everyone): ",
+            )?);
+
+        writeln!(
+            self.output,
+            "0 => Full name\n1 => First name\n2 => Last name"
+        )
+        .map_err(InputError::IOError)?;
+        options.field = match self.optional_int_input_prompt(
+            "Search by (leave empty for full name): ",
+            0,
+            2,
+        )? {
+            Some(1) => SearchField::First,
+            Some(2) => SearchField::Last,
+            _ => SearchField::Full,
+        };
+
+        writeln!(self.output, "0 => Contains\n1 => Starts with\n2 => Fuzzy")
+            .map_err(InputError::IOError)?;
+        options.mode = match self.optional_int_input_prompt(
+            "Match mode (leave empty for contains): ",
+            0,
+            2,
+        )? {
+            Some(1) => MatchMode::Prefix,
+            Some(2) => MatchMode::Fuzzy(customer_search::DEFAULT_MAX_DISTANCE),
+            _ => MatchMode::Contains,
+        };
+
+        options.ignore_case = !self
+            .string_input_prompt("Match case? (Y/N): ")?
+            .eq_ignore_ascii_case("y");
+
+        let max_items = shop::STOCK_MAX_THRESHOLD as isize;
+        options.min_items = self
+            .optional_int_input_prompt("Minimum items (leave empty for any): ", 1, max_items)?
+            .map(|value| value as usize);
+        options.max_items = self
+            .optional_int_input_prompt("Maximum items (leave empty for any): ", 1, max_items)?
+            .map(|value| value as usize);
+
+        Ok(options)
+    }
+
+    fn save_path_prompt(&mut self) -> Result<Option<PathBuf>, InputError> {
+        let input =
+            self.string_input_prompt("Enter a file path or slot name (leave empty for default): ")?;
+
+        if input.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(storage::resolve_save_path(&input)))
+        }
+    }
+
+    fn spd(&mut self) -> io::Result<()> {
+        self.display_header("Save Program Data")?;
+
+        let save_path = match self.save_path_prompt() {
+            Ok(path) => path.unwrap_or_else(|| PathBuf::from(DEFAULT_SAVE_PATH)),
+            Err(error) => return self.handle_input_error(error),
+        };
+
+        let result = storage::write_save(&save_path, &self.shop.borrow());
+
+        match result {
+            Ok(_) => {
+                writeln!(
+                    self.output,
+                    "Successfully saved data to {}",
+                    save_path.display()
+                )?;
+                self.checkpoint_journal(Some(&save_path))
+            }
+            Err(error) => self.handle_io_error(error),
+        }
+    }
+
+    fn lpd(&mut self) -> io::Result<()> {
+        self.display_header("Load Program Data")?;
+
+        let save_path = match self.save_path_prompt() {
+            Ok(Some(path)) => path,
+            Ok(None) if Path::new(DEFAULT_SAVE_PATH).exists() => PathBuf::from(DEFAULT_SAVE_PATH),
+            Ok(None) => PathBuf::from(LEGACY_SAVE_PATH),
+            Err(error) => return self.handle_input_error(error),
+        };
+
+        self.load_file(&save_path).map(|_| ())
+    }
+
+    /// Loads a save file and makes it the base of the journal, returning whether it succeeded.
+    pub fn load_file(&mut self, path: &Path) -> io::Result<bool> {
+        match self.load_from_path(path) {
+            Ok(_) => {
+                writeln!(
+                    self.output,
+                    "Successfully loaded data from {}",
+                    path.display()
+                )?;
+                self.checkpoint_journal(Some(path))?;
+                Ok(true)
+            }
             Err(error) => {
-                Self::handle_input_error(error);
-                return;
+                self.handle_load_error(error)?;
+                Ok(false)
+            }
+        }
+    }
+
+    fn load_from_path(&mut self, path: &Path) -> Result<(), LoadError> {
+        let mut save_file = File::open(path).map_err(LoadError::IOError)?;
+
+        self.shop
+            .borrow_mut()
+            .load(&mut save_file, SaveFormat::from_path(path))
+    }
+
+    /// Starts the journal over on top of the given snapshot, or on top of the shop as it is now
+    /// when there is none.
+    fn checkpoint_journal(&mut self, snapshot: Option<&Path>) -> io::Result<()> {
+        let result = {
+            let shop = self.shop.borrow();
+
+            let start = match snapshot {
+                Some(path) => JournalEntry::Snapshot {
+                    path: path.to_path_buf(),
+                },
+                None => JournalEntry::Opened {
+                    queue_layout: shop.view_data().iter().map(FoodQueue::capacity).collect(),
+                    stock: shop
+                        .inventory()
+                        .get(DEFAULT_PRODUCT)
+                        .map_or(0, |product| product.stock()),
+                },
+            };
+
+            match shop.journal() {
+                Some(journal) => journal.checkpoint(&start),
+                None => return Ok(()),
+            }
+        };
+
+        if let Err(error) = result {
+            writeln!(self.output, "Failed to reset the change journal!")?;
+            self.handle_io_error(error)?;
+        }
+
+        Ok(())
+    }
+
+    /// Reads the journal entries along with how many of them are changes rather than starting
+    /// points. Returns `None` when there is no journal or it could not be read.
+    fn read_journal(&mut self) -> io::Result<Option<(Vec<JournalEntry>, usize)>> {
+        let result = self
+            .shop
+            .borrow()
+            .journal()
+            .map(|journal| journal.read_entries());
+
+        let entries = match result {
+            Some(Ok(entries)) => entries,
+            Some(Err(error)) => {
+                writeln!(self.output, "Failed to read the change journal!")?;
+                self.handle_io_error(error)?;
+                return Ok(None);
             }
+            None => return Ok(None),
         };
 
-        match self.shop.remove_customer(queue_no, customer_pos) {
-            Ok(customer) => println!("Successfully removed customer {}", customer.first_name()),
-            Err(error) => Self::handle_shop_error(error),
+        let no_changes = entries
+            .iter()
+            .filter(|entry| {
+                !matches!(
+                    entry,
+                    JournalEntry::Snapshot { .. } | JournalEntry::Opened { .. }
+                )
+            })
+            .count();
+
+        Ok(Some((entries, no_changes)))
+    }
+
+    /// Warns about changes that were not saved before the program last exited, for when another
+    /// save is about to be loaded in their place.
+    pub fn warn_unsaved_changes(&mut self, load_path: &Path) -> io::Result<()> {
+        match self.read_journal()? {
+            Some((_, no_changes)) if no_changes > 0 => writeln!(
+                self.output,
+                "Discarding {no_changes} unsaved changes from a previous session to load {}.",
+                load_path.display()
+            ),
+            _ => Ok(()),
         }
     }
 
-    fn pcq(&mut self) {
-        let queue_no =
-            match Self::int_input_prompt("Enter the queue number: ", 0, self.shop.len() as isize) {
-                Ok(value) => value as usize,
-                Err(error) => {
-                    Self::handle_input_error(error);
-                    return;
+    /// Offers to replay changes that were not saved before the program last exited. The journal
+    /// is started over when there are none, so that it records what this run opened with.
+    pub fn recover_journal(&mut self) -> io::Result<()> {
+        let (entries, no_changes) = match self.read_journal()? {
+            Some(journal) => journal,
+            None => return Ok(()),
+        };
+
+        if no_changes == 0 {
+            return self.checkpoint_journal(None);
+        }
+
+        self.display_header("Recover Unsaved Changes")?;
+        writeln!(
+            self.output,
+            "Found {no_changes} unsaved changes from a previous session."
+        )?;
+
+        let answer = match self.string_input_prompt("Replay them? (Y/N): ") {
+            Ok(value) => value,
+            Err(error) => return self.handle_input_error(error),
+        };
+
+        if !answer.eq_ignore_ascii_case("y") {
+            writeln!(self.output, "Discarding unsaved changes.")?;
+            return self.checkpoint_journal(None);
+        }
+
+        // A failed recovery leaves the journal describing changes the shop no longer has, so it
+        // starts over on top of whatever the shop holds instead
+        match entries.first() {
+            Some(JournalEntry::Snapshot { path }) => {
+                if let Err(error) = self.load_from_path(path) {
+                    self.handle_load_error(error)?;
+                    return self.checkpoint_journal(None);
                 }
-            };
+            }
+            Some(JournalEntry::Opened {
+                queue_layout,
+                stock,
+            }) => self.shop.borrow_mut().reopen(queue_layout, *stock),
+            _ => {}
+        }
+
+        let result = self.shop.borrow_mut().replay_journal(&entries);
+
+        match result {
+            Ok(_) => writeln!(self.output, "Successfully replayed {no_changes} changes."),
+            Err(error) => {
+                self.handle_shop_error(error)?;
+                self.checkpoint_journal(None)
+            }
+        }
+    }
+
+    fn lsd(&mut self) -> io::Result<()> {
+        self.display_header("Saved Program Data")?;
+
+        let mut saves = Vec::new();
+
+        for path in [DEFAULT_SAVE_PATH, LEGACY_SAVE_PATH] {
+            if !Path::new(path).exists() {
+                continue;
+            }
+
+            match SaveInfo::read("default".to_string(), PathBuf::from(path)) {
+                Ok(save) => saves.push(save),
+                Err(error) => self.handle_io_error(error)?,
+            }
+        }
+
+        match storage::list_slots() {
+            Ok(slots) => saves.extend(slots),
+            Err(error) => self.handle_io_error(error)?,
+        }
+
+        if saves.is_empty() {
+            return writeln!(self.output, "No saved data found.");
+        }
+
+        for save in saves {
+            self.display_save_info(&save)?;
+        }
+
+        Ok(())
+    }
+
+    fn display_save_info(&mut self, save: &SaveInfo) -> io::Result<()> {
+        let modified = save
+            .modified
+            .map(|time| {
+                DateTime::<Local>::from(time)
+                    .format("%Y-%m-%d %H:%M:%S")
+                    .to_string()
+            })
+            .unwrap_or_else(|| "Unknown".to_string());
+
+        writeln!(
+            self.output,
+            "Name: {}\nPath: {}\nSaved: {}",
+            save.name,
+            save.path.display(),
+            modified
+        )?;
 
-        match self.shop.serve_customer(queue_no) {
-            Ok(customer) => println!(
-                "Customer {} was served {} items!",
-                customer.first_name(),
-                customer.no_items()
+        match save.counts {
+            Some((queue_count, customer_count)) => writeln!(
+                self.output,
+                "Queues: {queue_count}\nCustomers: {customer_count}"
             ),
-            Err(error) => Self::handle_shop_error(error),
+            None => writeln!(self.output, "Save file could not be read!"),
         }
     }
 
-    fn vcs(&self) {
-        Self::display_header("Sorted Customers List");
+    fn rbk(&mut self) -> io::Result<()> {
+        self.display_header("Restore From Backup")?;
 
-        let sorted_customers = self.shop.get_sorted_customers();
-        for customer in sorted_customers {
-            println!(
-                "Name: {}\nItems: {}",
-                customer.full_name(),
-                customer.no_items()
-            );
+        let save_path = match self.save_path_prompt() {
+            Ok(path) => path.unwrap_or_else(|| PathBuf::from(DEFAULT_SAVE_PATH)),
+            Err(error) => return self.handle_input_error(error),
+        };
+
+        let backups = match storage::list_backups(&save_path) {
+            Ok(backups) => backups,
+            Err(error) => return self.handle_io_error(error),
+        };
+
+        if backups.is_empty() {
+            return writeln!(self.output, "No backups found for {}", save_path.display());
+        }
+
+        for backup in backups.as_slice() {
+            self.display_save_info(backup)?;
+        }
+
+        let backup_path = match self.int_input_prompt(
+            "Enter the backup number to restore: ",
+            1,
+            backups.len() as isize,
+        ) {
+            Ok(value) => backups[value as usize - 1].path.clone(),
+            Err(error) => return self.handle_input_error(error),
+        };
+
+        match self.load_from_path(&backup_path) {
+            Ok(_) => {
+                writeln!(
+                    self.output,
+                    "Successfully restored data from {}",
+                    backup_path.display()
+                )?;
+                self.checkpoint_journal(Some(&backup_path))
+            }
+            Err(error) => self.handle_load_error(error),
+        }
+    }
+
+    fn stk(&mut self) -> io::Result<()> {
+        self.display_header("Current Stock Level")?;
+
+        let shop = Rc::clone(&self.shop);
+        let shop = shop.borrow();
+        for product in shop.inventory().products() {
+            writeln!(
+                self.output,
+                "Product: {}\nStock: {}\nPrice: {}\nLow Threshold: {}\nMax Threshold: {}",
+                product.name(),
+                product.stock(),
+                product.price(),
+                product.low_threshold(),
+                product.max_threshold()
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn afs(&mut self) -> io::Result<()> {
+        self.display_header("Add To Stock")?;
+
+        let product = match self.product_prompt() {
+            Ok(value) => value,
+            Err(error) => return self.handle_input_error(error),
+        };
+
+        let new_stock = match self.int_input_prompt(
+            &format!("Enter {} stock amount to add: ", product.name()),
+            0,
+            (product.max_threshold() - product.stock()) as isize,
+        ) {
+            Ok(value) => product.stock() + value as usize,
+            Err(error) => return self.handle_input_error(error),
+        };
+
+        let result =
+            self.shop
+                .borrow_mut()
+                .set_stock(product.name(), new_stock, StockReason::Delivery);
+
+        match result {
+            Ok(_) => Ok(()),
+            Err(error) => self.handle_shop_error(error),
+        }
+    }
+
+    fn adj(&mut self) -> io::Result<()> {
+        self.display_header("Adjust Stock")?;
+
+        let product = match self.product_prompt() {
+            Ok(value) => value,
+            Err(error) => return self.handle_input_error(error),
+        };
+
+        writeln!(self.output, "0 => Correction\n1 => Wastage")?;
+        let reason = match self.int_input_prompt("Enter the reason: ", 0, 1) {
+            Ok(0) => StockReason::Correction,
+            Ok(_) => StockReason::Wastage,
+            Err(error) => return self.handle_input_error(error),
+        };
+
+        let new_stock = match reason {
+            StockReason::Wastage => match self.int_input_prompt(
+                &format!("Enter {} stock amount wasted: ", product.name()),
+                0,
+                product.stock() as isize,
+            ) {
+                Ok(value) => product.stock() - value as usize,
+                Err(error) => return self.handle_input_error(error),
+            },
+            _ => match self.int_input_prompt(
+                &format!("Enter the counted {} stock: ", product.name()),
+                0,
+                product.max_threshold() as isize,
+            ) {
+                Ok(value) => value as usize,
+                Err(error) => return self.handle_input_error(error),
+            },
+        };
+
+        let result = self
+            .shop
+            .borrow_mut()
+            .set_stock(product.name(), new_stock, reason);
+
+        match result {
+            Ok(_) => writeln!(self.output, "{} stock is now {new_stock}.", product.name()),
+            Err(error) => self.handle_shop_error(error),
         }
     }
 
-    fn spd(&self) {
-        Self::display_header("Save Program Data");
+    fn vsl(&mut self) -> io::Result<()> {
+        self.display_header("Stock Log")?;
+
+        let shop = self.shop.borrow();
+        let changes = shop.stock_log().changes();
 
-        let mut save_file = File::create(DEFAULT_SAVE_PATH).unwrap();
-        self.shop.save_to_file(&mut save_file).unwrap();
+        if changes.is_empty() {
+            return writeln!(self.output, "No stock changes recorded.");
+        }
+
+        for change in changes {
+            writeln!(
+                self.output,
+                "Time: {}\nProduct: {}\nReason: {}\nQuantity: {:+}\nStock: {}\nOperator: {}",
+                change.timestamp.format("%Y-%m-%d %H:%M:%S"),
+                change.product,
+                change.reason,
+                change.quantity,
+                change.stock,
+                change.operator
+            )?;
+        }
 
-        println!("Successfully saved data to {DEFAULT_SAVE_PATH}");
+        Ok(())
     }
 
-    fn lpd(&mut self) {
-        Self::display_header("Load Program Data");
+    fn esl(&mut self) -> io::Result<()> {
+        self.display_header("Export Stock Log")?;
 
-        let mut save_file = File::open(DEFAULT_SAVE_PATH).unwrap();
-        self.shop.load_from_file(&mut save_file);
+        let export_path =
+            match self.string_input_prompt("Enter a file path (leave empty for default): ") {
+                Ok(path) if path.is_empty() => PathBuf::from(stock_log::DEFAULT_EXPORT_PATH),
+                Ok(path) => PathBuf::from(path),
+                Err(error) => return self.handle_input_error(error),
+            };
+
+        let result = File::create(&export_path)
+            .and_then(|file| self.shop.borrow().stock_log().export_csv(file));
 
-        println!("Successfully loaded data from {DEFAULT_SAVE_PATH}");
+        match result {
+            Ok(_) => writeln!(
+                self.output,
+                "Exported the stock log to {}",
+                export_path.display()
+            ),
+            Err(error) => self.handle_io_error(error),
+        }
     }
 
-    fn stk(&self) {
-        Self::display_header("Current Stock Level");
+    fn rsr(&mut self) -> io::Result<()> {
+        self.display_header("Stock Reconciliation")?;
 
-        println!("Stock: {}", self.shop.stock());
+        let report = {
+            let shop = self.shop.borrow();
+            shop.stock_log().reconcile(shop.inventory())
+        };
+
+        for (product, reconciliation) in report.iter() {
+            writeln!(
+                self.output,
+                "Product: {product}\nOpening: {}\nDelivered: {}\nSold: {}\nWasted: {}\n\
+                 Expected: {}\nActual: {}\nDifference: {:+}\nExplained by corrections: {:+}\n\
+                 Unexplained: {:+}",
+                reconciliation.opening,
+                reconciliation.delivered,
+                reconciliation.sold,
+                reconciliation.wasted,
+                reconciliation.expected,
+                reconciliation.actual,
+                reconciliation.difference,
+                reconciliation.corrected,
+                reconciliation.unexplained
+            )?;
+        }
+
+        Ok(())
     }
 
-    fn afs(&mut self) {
-        Self::display_header("Add To Stock");
+    fn apr(&mut self) -> io::Result<()> {
+        self.display_header("Add Product")?;
 
-        let new_stock = match Self::int_input_prompt(
-            "Enter stock amount to add: ",
+        let name = match self.string_input_prompt("Enter the product name: ") {
+            Ok(value) => value,
+            Err(error) => return self.handle_input_error(error),
+        };
+        let price = match self.int_input_prompt("Enter the price: ", 0, isize::MAX) {
+            Ok(value) => value as usize,
+            Err(error) => return self.handle_input_error(error),
+        };
+        let max_threshold = match self.int_input_prompt("Enter the maximum stock: ", 1, isize::MAX)
+        {
+            Ok(value) => value as usize,
+            Err(error) => return self.handle_input_error(error),
+        };
+        let low_threshold = match self.int_input_prompt(
+            "Enter the low stock threshold: ",
             0,
-            (shop::STOCK_MAX_THRESHOLD - self.shop.stock()) as isize,
+            max_threshold as isize - 1,
         ) {
-            Ok(value) => self.shop.stock() + value as usize,
-            Err(error) => {
-                Self::handle_input_error(error);
-                return;
+            Ok(value) => value as usize,
+            Err(error) => return self.handle_input_error(error),
+        };
+
+        let result = Product::new(name, price, low_threshold, max_threshold)
+            .map_err(ShopError::InventoryError)
+            .and_then(|product| {
+                let name = product.name().to_string();
+                self.shop.borrow_mut().add_product(product).map(|_| name)
+            });
+
+        match result {
+            Ok(name) => writeln!(self.output, "Added {name} to the catalog."),
+            Err(error) => self.handle_shop_error(error),
+        }
+    }
+
+    fn describe_history_entry(entry: &HistoryEntry) -> String {
+        match entry {
+            HistoryEntry::AddCustomer {
+                queue_no, customer, ..
+            } => format!("adding {} to queue {queue_no}", customer.full_name()),
+            HistoryEntry::RemoveCustomer {
+                queue_no, customer, ..
+            } => format!("removing {} from queue {queue_no}", customer.full_name()),
+            HistoryEntry::ServeCustomer {
+                queue_no, customer, ..
+            } => format!("serving {} from queue {queue_no}", customer.full_name()),
+            HistoryEntry::WaitCustomer { customer } => {
+                format!("adding {} to the waiting list", customer.full_name())
             }
+            HistoryEntry::SetStock {
+                product,
+                previous_stock,
+                stock,
+                reason,
+            } => format!("changing {product} stock from {previous_stock} to {stock} ({reason})"),
+        }
+    }
+
+    fn und(&mut self) -> io::Result<()> {
+        self.display_header("Undo")?;
+
+        let result = self.shop.borrow_mut().undo();
+
+        match result {
+            Ok(entry) => writeln!(
+                self.output,
+                "Undid {}.",
+                Self::describe_history_entry(&entry)
+            ),
+            Err(error) => self.handle_shop_error(error),
+        }
+    }
+
+    fn red(&mut self) -> io::Result<()> {
+        self.display_header("Redo")?;
+
+        let result = self.shop.borrow_mut().redo();
+
+        match result {
+            Ok(entry) => writeln!(
+                self.output,
+                "Redid {}.",
+                Self::describe_history_entry(&entry)
+            ),
+            Err(error) => self.handle_shop_error(error),
+        }
+    }
+
+    fn qas(&mut self) -> io::Result<()> {
+        self.display_header("Queue Assignment Strategy")?;
+
+        let strategy_name = self.shop.borrow().strategy().name();
+        writeln!(self.output, "Current strategy: {strategy_name}")?;
+
+        let mut strategies = queue_strategy::built_in_strategies();
+        for (i, strategy) in strategies.iter().enumerate() {
+            writeln!(self.output, "{i} => {}", strategy.name())?;
+        }
+
+        let strategy_no = match self.int_input_prompt(
+            "Enter the strategy number: ",
+            0,
+            strategies.len() as isize - 1,
+        ) {
+            Ok(value) => value as usize,
+            Err(error) => return self.handle_input_error(error),
+        };
+
+        let strategy = strategies.swap_remove(strategy_no);
+        writeln!(self.output, "Switched to the {} strategy.", strategy.name())?;
+        self.shop.borrow_mut().set_strategy(strategy);
+
+        Ok(())
+    }
+
+    fn rpq(&mut self) -> io::Result<()> {
+        let revenue = self.shop.borrow().ledger().by_queue();
+        self.display_revenue("Revenue Per Queue", "Queue", &revenue)
+    }
+
+    fn rpd(&mut self) -> io::Result<()> {
+        let revenue = self.shop.borrow().ledger().by_day();
+        self.display_revenue("Revenue Per Day", "Day", &revenue)
+    }
+
+    fn rpc(&mut self) -> io::Result<()> {
+        let revenue = self.shop.borrow().ledger().by_customer();
+        self.display_revenue("Revenue Per Customer", "Customer", &revenue)
+    }
+
+    fn display_revenue<K: Display>(
+        &mut self,
+        title: &str,
+        group: &str,
+        revenue: &BTreeMap<K, Revenue>,
+    ) -> io::Result<()> {
+        self.display_header(title)?;
+
+        if revenue.is_empty() {
+            writeln!(self.output, "No sales recorded.")?;
+        }
+
+        for (key, revenue) in revenue.iter() {
+            writeln!(
+                self.output,
+                "{group}: {key}\nSales: {}\nItems: {}\nItems Short: {}\nRevenue: {}",
+                revenue.no_sales, revenue.no_items, revenue.items_short, revenue.revenue
+            )?;
+        }
+
+        let total = self.shop.borrow().ledger().total();
+        writeln!(
+            self.output,
+            "Total Sales: {}\nTotal Items: {}\nTotal Items Short: {}\nTotal Revenue: {}",
+            total.no_sales, total.no_items, total.items_short, total.revenue
+        )
+    }
+
+    fn psp(&mut self) -> io::Result<()> {
+        self.display_header("Partial Serve Policy")?;
+
+        let policy_name = self.shop.borrow().partial_serve_policy().name();
+        writeln!(self.output, "Current policy: {policy_name}")?;
+
+        for (i, policy) in PartialServePolicy::ALL.iter().enumerate() {
+            writeln!(self.output, "{i} => {}", policy.name())?;
+        }
+
+        let policy_no = match self.int_input_prompt(
+            "Enter the policy number: ",
+            0,
+            PartialServePolicy::ALL.len() as isize - 1,
+        ) {
+            Ok(value) => value as usize,
+            Err(error) => return self.handle_input_error(error),
         };
 
-        self.shop.set_stock(new_stock);
+        let policy = PartialServePolicy::ALL[policy_no];
+        let result = self.shop.borrow_mut().set_partial_serve_policy(policy);
+
+        match result {
+            Ok(_) => writeln!(self.output, "Switched to the {} policy.", policy.name()),
+            Err(error) => self.handle_shop_error(error),
+        }
     }
 
-    fn gui(&self) {
-        // Inefficient as hell
-        Self::display_header("Starting GUI");
+    pub fn gui(&mut self) -> io::Result<()> {
+        self.display_header("Starting GUI")?;
 
-        let data = self.shop.clone();
-        let app = adw::Application::builder().application_id(APP_ID).build();
+        // A fresh application is built on every launch since one cannot run again after quitting.
+        // It is not registered as unique so a previous run never has to be torn down first.
+        let shop = Rc::clone(&self.shop);
+        let app = adw::Application::builder()
+            .application_id(APP_ID)
+            .flags(gio::ApplicationFlags::NON_UNIQUE)
+            .build();
 
         app.connect_activate(move |app| {
-            let window = GuiWindow::new(data.clone());
+            let window = GuiWindow::new(Rc::clone(&shop));
             window.set_application(Some(app));
             window.set_title(Some("Queue Viewer"));
+            app.set_accels_for_action("win.undo", &["<Control>z"]);
+            app.set_accels_for_action("win.redo", &["<Control><Shift>z", "<Control>y"]);
 
             window.present();
         });
 
-        app.run();
+        // The program arguments are not meant for GTK
+        app.run_with_args(&[] as &[&str]);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, io::Cursor};
+
+    use super::*;
+    use crate::journal::Journal;
+
+    const ADD_JANE: &str = "ACQ\nJane\nDoe\n3\n\n\n";
+
+    /// Runs the commands in `input` and returns the shop along with everything that was printed.
+    fn run_transcript(shop: Shop, input: &str) -> (Shop, String) {
+        let mut text_interface =
+            TextInterface::with_io(shop, Cursor::new(input.as_bytes()), Vec::new());
+        text_interface.run().unwrap();
+
+        let output = String::from_utf8(text_interface.output).unwrap();
+        (text_interface.shop.take(), output)
+    }
+
+    fn stock(shop: &Shop) -> usize {
+        shop.inventory().get(DEFAULT_PRODUCT).unwrap().stock()
+    }
+
+    struct BrokenWriter;
+
+    impl Write for BrokenWriter {
+        fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+            Err(io::ErrorKind::BrokenPipe.into())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Err(io::ErrorKind::BrokenPipe.into())
+        }
+    }
+
+    #[test]
+    fn adds_a_customer() {
+        let (shop, output) = run_transcript(Shop::new(&[2]).with_stock(10), ADD_JANE);
+
+        assert!(output.contains("Successfully added to queue 0."));
+        assert_eq!(shop.view_data()[0].len(), 1);
+        assert_eq!(shop.view_data()[0].view_data()[0].full_name(), "Jane Doe");
+        assert_eq!(shop.view_data()[0].view_data()[0].no_items(), 3);
+    }
+
+    #[test]
+    fn serves_a_customer() {
+        let input = format!("{ADD_JANE}PCQ\n0\n");
+        let (shop, output) = run_transcript(Shop::new(&[2]).with_stock(10), &input);
+
+        assert!(output.contains("Customer Jane was served"));
+        assert!(shop.view_data()[0].is_empty());
+        assert_eq!(stock(&shop), 7);
+    }
+
+    #[test]
+    fn removes_a_customer() {
+        let input = format!("{ADD_JANE}RCQ\n0\n1\nRCQ\n0\n0\nRCQ\n0\n");
+        let (shop, output) = run_transcript(Shop::new(&[2]).with_stock(10), &input);
+
+        assert!(output.contains("Input must be between 0 and 0"));
+        assert!(output.contains("Successfully removed customer Jane"));
+        assert!(output.contains("Queue is empty!"));
+        assert!(shop.view_data()[0].is_empty());
+    }
+
+    #[test]
+    fn rejects_a_queue_number_past_the_last_queue() {
+        let input = format!("{ADD_JANE}PCQ\n1\nRCQ\n1\n");
+        let (shop, output) = run_transcript(Shop::new(&[2]).with_stock(10), &input);
+
+        assert_eq!(
+            output
+                .matches("Input is out of range! Input must be between 0 and 0")
+                .count(),
+            2
+        );
+        assert_eq!(shop.view_data()[0].len(), 1);
+    }
+
+    #[test]
+    fn views_the_queues() {
+        let input = format!("{ADD_JANE}VFQ\n");
+        let (_, output) = run_transcript(Shop::new(&[2, 1]).with_stock(10), &input);
+
+        let rows = output
+            .lines()
+            .skip_while(|line| !line.contains("View All The Queues"))
+            .skip(2)
+            .take(2)
+            .map(|line| line.split_whitespace().collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+
+        assert_eq!(rows, [vec!["X", "O"], vec!["O"]]);
+    }
+
+    #[test]
+    fn views_the_queues_of_a_loaded_layout() {
+        let path = env::temp_dir().join(format!("sdii_cw_layout_test_{}.txt", std::process::id()));
+        fs::write(&path, "5\n1\n0\n5\n0\n").unwrap();
+
+        let input = format!("LPD\n{}\nVFQ\n", path.display());
+        let (_, output) = run_transcript(Shop::new(&[2]), &input);
+        fs::remove_file(&path).unwrap();
+
+        let rows = output
+            .lines()
+            .skip_while(|line| !line.contains("View All The Queues"))
+            .skip(2)
+            .take_while(|line| line.trim() == "O")
+            .count();
+
+        assert_eq!(rows, 5);
+    }
+
+    #[test]
+    fn undoes_the_last_change() {
+        let input = format!("{ADD_JANE}PCQ\n0\nUND\nUND\n");
+        let (shop, output) = run_transcript(Shop::new(&[2]).with_stock(10), &input);
+
+        assert_eq!(output.matches("Undid").count(), 2);
+        assert!(shop.view_data()[0].is_empty());
+        assert_eq!(stock(&shop), 10);
+        assert!(!shop.history().can_undo());
+    }
+
+    #[test]
+    fn stops_at_the_end_of_input() {
+        let (_, output) = run_transcript(Shop::new(&[2]), "VFQ\n");
+        assert!(output.ends_with("Exiting Text Interface...\n"));
+
+        let (shop, output) = run_transcript(Shop::new(&[2]), "ACQ\nJane\n");
+        assert!(output.contains("Input ended before the command was complete!"));
+        assert!(output.ends_with("Exiting Text Interface...\n"));
+        assert!(shop.view_data()[0].is_empty());
+    }
+
+    #[test]
+    fn stops_when_the_output_fails() {
+        let mut text_interface =
+            TextInterface::with_io(Shop::new(&[2]), Cursor::new(&b"VFQ\n"[..]), BrokenWriter);
+
+        let error = text_interface.run().unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::BrokenPipe);
+    }
+
+    #[test]
+    fn starts_the_journal_over_when_recovery_fails() {
+        let path = env::temp_dir().join(format!("sdii_cw_recovery_test_{}", std::process::id()));
+        let journal = Journal::new(&path);
+        let removal = JournalEntry::RemoveCustomer {
+            queue_no: 0,
+            customer_pos: 0,
+        };
+
+        // Neither the missing snapshot nor removing from an empty queue can be recovered
+        let starts = [
+            JournalEntry::Snapshot {
+                path: env::temp_dir().join("sdii_cw_missing_snapshot.txt"),
+            },
+            JournalEntry::Opened {
+                queue_layout: vec![2],
+                stock: 5,
+            },
+        ];
+
+        for start in starts {
+            journal.checkpoint(&start).unwrap();
+            journal.append(&removal).unwrap();
+
+            let mut shop = Shop::new(&[3]);
+            shop.set_journal(Some(Journal::new(&path)));
+            let mut text_interface =
+                TextInterface::with_io(shop, Cursor::new(&b"Y\n"[..]), Vec::new());
+            text_interface.recover_journal().unwrap();
+
+            let entries = journal.read_entries().unwrap();
+            assert_eq!(entries.len(), 1);
+            assert!(matches!(entries[0], JournalEntry::Opened { .. }));
+        }
+
+        fs::remove_file(&path).unwrap();
     }
 }